@@ -0,0 +1,230 @@
+use std::fmt;
+
+use crate::rules::Rules;
+
+/// The number of ways to continue from the joltage to the device doesn't
+/// fit in a `u128`.
+#[derive(Debug, PartialEq)]
+pub struct Overflow {
+    pub joltage: u32,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "number of arrangements overflows u128 at {} jolts", self.joltage)
+    }
+}
+
+/// The indices in the chain that can follow index `i` directly.
+pub fn successors<'a>(chain: &'a [u32], rules: &'a Rules, i: usize) -> impl Iterator<Item = usize> + 'a {
+    // NOTE: Only adapters below the outlet are out of order, and they are never a successor.
+    (i + 1..chain.len())
+        .skip_while(move |k| chain[*k] < chain[i])
+        .take_while(move |k| chain[*k] - chain[i] <= *rules.gaps.end())
+        .filter(move |k| chain[*k] - chain[i] >= *rules.gaps.start())
+}
+
+/// For every joltage in the chain, the number of ways to continue from it to
+/// the device. The first entry is the number of arrangements.
+pub fn ways_to_device(chain: &[u32], rules: &Rules) -> Result<Vec<u128>, Overflow> {
+    let mut ways: Vec<u128> = vec![0; chain.len()];
+    if let Some(last) = ways.last_mut() {
+        *last = 1;
+    }
+
+    for i in (0..chain.len().saturating_sub(1)).rev() {
+        ways[i] = successors(chain, rules, i)
+            .try_fold(0u128, |total, k| total.checked_add(ways[k]))
+            .ok_or(Overflow { joltage: chain[i] })?;
+    }
+
+    Ok(ways)
+}
+
+/// The number of distinct ways to connect the outlet to the device.
+pub fn count_arrangements(chain: &[u32], rules: &Rules) -> Result<u128, Overflow> {
+    Ok(ways_to_device(chain, rules)?.first().copied().unwrap_or(0))
+}
+
+/// For every joltage in the chain, whether the device can be reached from
+/// it. Unlike `ways_to_device` this never overflows.
+pub fn reaches_device(chain: &[u32], rules: &Rules) -> Vec<bool> {
+    let mut reaches = vec![false; chain.len()];
+    if let Some(last) = reaches.last_mut() {
+        *last = true;
+    }
+
+    for i in (0..chain.len().saturating_sub(1)).rev() {
+        reaches[i] = successors(chain, rules, i).any(|k| reaches[k]);
+    }
+
+    reaches
+}
+
+/// Lazily walks every arrangement in order, each as the joltages from the
+/// outlet to the device.
+///
+/// This is a depth-first search that only descends into joltages that can
+/// still reach the device, so every branch it takes ends in an arrangement.
+pub struct Arrangements<'a> {
+    chain: &'a [u32],
+    rules: &'a Rules,
+    reaches: Vec<bool>,
+    /// The indices on the current path.
+    path: Vec<usize>,
+}
+
+impl<'a> Arrangements<'a> {
+    pub fn new(chain: &'a [u32], rules: &'a Rules) -> Arrangements<'a> {
+        let reaches = reaches_device(chain, rules);
+        let path = if reaches.first().copied().unwrap_or(false) { vec![0] } else { vec![] };
+
+        Arrangements { chain, rules, reaches, path }
+    }
+
+    /// The first successor of `i` after `after` that reaches the device.
+    fn next_step(&self, i: usize, after: Option<usize>) -> Option<usize> {
+        successors(self.chain, self.rules, i)
+            .skip_while(|k| Some(*k) <= after)
+            .find(|k| self.reaches[*k])
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Vec<u32>> {
+        let device = self.chain.len() - 1;
+
+        // NOTE: Unless this is the first call, the path ends at the device, so
+        // backtrack to the deepest joltage with another way forward.
+        if self.path.last() == Some(&device) {
+            loop {
+                let last = self.path.pop()?;
+                let parent = *self.path.last()?;
+                if let Some(next) = self.next_step(parent, Some(last)) {
+                    self.path.push(next);
+                    break;
+                }
+            }
+        }
+
+        let mut current = *self.path.last()?;
+        while current != device {
+            current = self.next_step(current, None).unwrap();
+            self.path.push(current);
+        }
+
+        Some(self.path.iter().map(|i| self.chain[*i]).collect())
+    }
+}
+
+/// The arrangement using the fewest adapters, or the most with `longest`.
+pub fn extreme_arrangement(chain: &[u32], rules: &Rules, longest: bool) -> Option<Vec<u32>> {
+    let reaches = reaches_device(chain, rules);
+    if !reaches.first().copied().unwrap_or(false) {
+        return None;
+    }
+
+    // NOTE: `steps[i]` is the best number of steps from `i` to the device.
+    let device = chain.len() - 1;
+    let mut steps = vec![0; chain.len()];
+    let mut best_next = vec![device; chain.len()];
+    for i in (0..device).rev() {
+        let candidates = successors(chain, rules, i).filter(|k| reaches[*k]);
+        let best = if longest {
+            candidates.max_by_key(|k| (steps[*k], std::cmp::Reverse(*k)))
+        } else {
+            candidates.min_by_key(|k| (steps[*k], *k))
+        };
+
+        if let Some(k) = best {
+            steps[i] = steps[k] + 1;
+            best_next[i] = k;
+        }
+    }
+
+    let mut path = vec![chain[0]];
+    let mut i = 0;
+    while i != device {
+        i = best_next[i];
+        path.push(chain[i]);
+    }
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::parse_adapters;
+
+    #[test]
+    fn test_examples() {
+        let rules = Rules::default();
+
+        let small = rules.chain(&parse_adapters(include_str!("small_example_day10.txt"))).unwrap();
+        assert_eq!(count_arrangements(&small, &rules), Ok(8));
+
+        let example = rules.chain(&parse_adapters(include_str!("example_day10.txt"))).unwrap();
+        assert_eq!(count_arrangements(&example, &rules), Ok(19208));
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let rules = Rules::default();
+
+        let small = rules.chain(&parse_adapters(include_str!("small_example_day10.txt"))).unwrap();
+        let arrangements: Vec<Vec<u32>> = Arrangements::new(&small, &rules).collect();
+        assert_eq!(arrangements.len(), 8);
+        assert_eq!(arrangements[0], small);
+        assert_eq!(arrangements[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+
+        let example = rules.chain(&parse_adapters(include_str!("example_day10.txt"))).unwrap();
+        assert_eq!(Arrangements::new(&example, &rules).count(), 19208);
+        assert_eq!(ways_to_device(&example, &rules).unwrap()[0], 19208);
+
+        let impossible = rules.chain(&[1, 9]).unwrap();
+        assert_eq!(Arrangements::new(&impossible, &rules).next(), None);
+        assert_eq!(extreme_arrangement(&impossible, &rules, false), None);
+    }
+
+    #[test]
+    fn test_extremes() {
+        let rules = Rules::default();
+        let small = rules.chain(&parse_adapters(include_str!("small_example_day10.txt"))).unwrap();
+
+        assert_eq!(extreme_arrangement(&small, &rules, true), Some(small.clone()));
+        assert_eq!(
+            extreme_arrangement(&small, &rules, false),
+            Some(vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22])
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        // NOTE: With gaps of exactly 2 the odd adapters can't be used at all.
+        let rules = Rules { outlet: 0, device_offset: 2, gaps: 2..=2 };
+        assert_eq!(count_arrangements(&rules.chain(&[1, 2, 3, 4]).unwrap(), &rules), Ok(1));
+
+        let rules = Rules { outlet: 2, device_offset: 3, gaps: 1..=3 };
+        assert_eq!(count_arrangements(&rules.chain(&[1, 3, 4]).unwrap(), &rules), Ok(2));
+        assert_eq!(count_arrangements(&rules.chain(&[1, 9]).unwrap(), &rules), Ok(0));
+    }
+
+    #[test]
+    fn test_large() {
+        // NOTE: 100 adapters one jolt apart have more arrangements than fit in a
+        // u64, and 200 more than fit in a u128.
+        let rules = Rules::default();
+        let adapters: Vec<u32> = (1..=100).collect();
+        assert!(count_arrangements(&rules.chain(&adapters).unwrap(), &rules).unwrap() > u64::MAX as u128);
+
+        let adapters: Vec<u32> = (1..=200).collect();
+        let chain = rules.chain(&adapters).unwrap();
+        assert_eq!(count_arrangements(&chain, &rules), Err(Overflow { joltage: 53 }));
+        assert_eq!(extreme_arrangement(&chain, &rules, false).map(|a| a.len()), Some(69));
+        assert_eq!(Arrangements::new(&chain, &rules).next(), Some(chain));
+    }
+}
@@ -1,39 +1,167 @@
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{self, BufReader};
-use std::collections::HashMap;
+use std::io::{self};
+use std::env;
+
+mod arrangements;
+mod rules;
+mod sample;
+
+use arrangements::Arrangements;
+use rules::Rules;
+
+enum Command {
+    Solve,
+    List,
+    Sample,
+}
+
+struct Options {
+    command: Command,
+    input: String,
+    rules: Rules,
+    limit: usize,
+    seed: u64,
+}
 
 fn main() -> io::Result<()> {
-    let f = File::open("src/day10/input_day10.txt")?;
-    let f = BufReader::new(f);
+    let args: Vec<String> = env::args().collect();
+    let options = parse_options(&args);
+    let rules = &options.rules;
 
-    let mut adapters: Vec<u32> = vec![];
+    let mut f = File::open(&options.input)?;
+    let mut s = String::new();
+    f.read_to_string(&mut s)?;
 
-    for line in f.lines() {
-        let line = line.unwrap();
+    let adapters = parse_adapters(&s);
+    let chain = match rules.chain(&adapters) {
+        Ok(chain) => chain,
+        Err(overflow) => {
+            eprintln!("No chain, {}", overflow);
+            std::process::exit(1);
+        }
+    };
 
-        adapters.push(line.parse().unwrap());
+    match options.command {
+        Command::Solve => (),
+        Command::List => {
+            for arrangement in Arrangements::new(&chain, rules).take(options.limit) {
+                println!("{}", format_arrangement(&arrangement));
+            }
+            return Ok(());
+        }
+        Command::Sample => {
+            let mut rng = sample::Rng::new(options.seed);
+            for _ in 0..options.limit {
+                match sample::sample(&chain, rules, &mut rng) {
+                    Ok(Some(arrangement)) => println!("{}", format_arrangement(&arrangement)),
+                    Ok(None) => println!("no arrangement connects the outlet to the device"),
+                    Err(overflow) => {
+                        println!("Can't sample, {}", overflow);
+                        break;
+                    }
+                }
+            }
+            return Ok(());
+        }
     }
 
-    adapters.sort();
-    adapters.push(adapters.last().unwrap() + 3);
+    let histogram = rules::histogram(&chain);
+    let differences: Vec<String> = histogram.iter().map(|(gap, n)| format!("{}: {}", gap, n)).collect();
+    println!("Differences: {}", differences.join(", "));
 
-    let mut count = HashMap::new();
-    let mut current = 0;
-    for i in 0..adapters.len() {
-        let next = adapters[i];
-        let difference = next - current;
+    match rules.validate(&chain) {
+        Ok(()) => {
+            let ones = histogram.get(&1).unwrap_or(&0);
+            let threes = histogram.get(&3).unwrap_or(&0);
 
-        let entry = count.entry(difference).or_insert(0);
-        *entry += 1;
-
-        current = next;
+            println!("Part 1: {}", ones * threes);
+        }
+        Err(gap) => println!("Part 1: no chain uses every adapter, {}", gap),
     }
 
-    let ones = count.get(&1).unwrap();
-    let threes = count.get(&3).unwrap();
+    match arrangements::count_arrangements(&chain, rules) {
+        Ok(count) => println!("Part 2: {}", count),
+        Err(overflow) => println!("Part 2: {}", overflow),
+    }
 
-    println!("{}", ones * threes);
+    for (name, longest) in [("Shortest", false), ("Longest", true)] {
+        if let Some(arrangement) = arrangements::extreme_arrangement(&chain, rules, longest) {
+            // NOTE: The outlet and the device aren't adapters.
+            println!("{} chain: {} adapters", name, arrangement.len() - 2);
+        }
+    }
 
     Ok(())
 }
+
+fn format_arrangement(arrangement: &[u32]) -> String {
+    let joltages: Vec<String> = arrangement.iter().map(|j| j.to_string()).collect();
+    joltages.join(" ")
+}
+
+/// `day10 [list|sample] [--input <file>] [--outlet <jolts>] [--device-offset <jolts>] [--gaps <min>..<max>]`
+///     `[--limit <n>] [--seed <n>]`
+///
+/// `list` prints the first `limit` arrangements in order, `sample` prints
+/// `limit` arrangements picked uniformly at random.
+fn parse_options(args: &[String]) -> Options {
+    let mut command = Command::Solve;
+    let mut input = String::from("src/day10/input_day10.txt");
+    let mut rules = Rules::default();
+    let mut limit = 10;
+    let mut seed = 0;
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                if let Some(path) = args.next() {
+                    input = path.clone();
+                }
+            }
+            "--outlet" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    rules.outlet = n;
+                }
+            }
+            "--device-offset" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    rules.device_offset = n;
+                }
+            }
+            "--gaps" => {
+                if let Some(gaps) = args.next().and_then(|gaps| parse_gaps(gaps)) {
+                    rules.gaps = gaps;
+                }
+            }
+            "--limit" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    limit = n;
+                }
+            }
+            "--seed" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    seed = n;
+                }
+            }
+            "list" => command = Command::List,
+            "sample" => command = Command::Sample,
+            _ => (),
+        }
+    }
+
+    Options { command, input, rules, limit, seed }
+}
+
+/// Parses `1..3` or `1..=3`, both including the end.
+fn parse_gaps(s: &str) -> Option<std::ops::RangeInclusive<u32>> {
+    let (min, max) = s.split_once("..")?;
+    let max = max.strip_prefix('=').unwrap_or(max);
+
+    Some(min.parse().ok()?..=max.parse().ok()?)
+}
+
+fn parse_adapters(s: &str) -> Vec<u32> {
+    s.lines().map(|line| line.parse().unwrap()).collect()
+}
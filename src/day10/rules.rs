@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// What the outlet, the device and the adapters in between accept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub outlet: u32,
+    /// How far above the highest adapter the device is rated.
+    pub device_offset: u32,
+    /// How much higher than its input an adapter's output may be.
+    pub gaps: RangeInclusive<u32>,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            outlet: 0,
+            device_offset: 3,
+            gaps: 1..=3,
+        }
+    }
+}
+
+/// Two neighbours in the chain whose difference the rules don't allow.
+#[derive(Debug, PartialEq)]
+pub struct InvalidGap {
+    pub from: u32,
+    pub to: u32,
+    pub gaps: RangeInclusive<u32>,
+}
+
+impl fmt::Display for InvalidGap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.to < self.from {
+            return write!(f, "adapter {} is below the outlet at {}", self.to, self.from);
+        }
+
+        write!(
+            f,
+            "gap of {} jolts from {} to {} is outside {}..={}",
+            self.to - self.from,
+            self.from,
+            self.to,
+            self.gaps.start(),
+            self.gaps.end()
+        )
+    }
+}
+
+/// The device's rating, `device_offset` above the highest adapter, doesn't
+/// fit in a `u32`.
+#[derive(Debug, PartialEq)]
+pub struct DeviceOverflow {
+    pub highest: u32,
+    pub device_offset: u32,
+}
+
+impl fmt::Display for DeviceOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "device rated {} jolts above {} overflows u32",
+            self.device_offset, self.highest
+        )
+    }
+}
+
+impl Rules {
+    /// The joltages from the outlet through every adapter to the device, in order.
+    pub fn chain(&self, adapters: &[u32]) -> Result<Vec<u32>, DeviceOverflow> {
+        let mut sorted = adapters.to_vec();
+        sorted.sort_unstable();
+
+        let highest = sorted.last().copied().unwrap_or(self.outlet);
+        let device = highest
+            .checked_add(self.device_offset)
+            .ok_or(DeviceOverflow { highest, device_offset: self.device_offset })?;
+
+        let mut chain = vec![self.outlet];
+        chain.extend(sorted);
+        chain.push(device);
+
+        Ok(chain)
+    }
+
+    /// Whether an adapter rated `to` can be plugged into one rated `from`.
+    pub fn allows(&self, from: u32, to: u32) -> bool {
+        to >= from && self.gaps.contains(&(to - from))
+    }
+
+    /// Checks that a chain using every adapter is possible, returning the
+    /// first pair of neighbours that breaks the rules otherwise. Adapters
+    /// below the outlet come right after it, so they show up as a decrease.
+    pub fn validate(&self, chain: &[u32]) -> Result<(), InvalidGap> {
+        match chain.windows(2).find(|pair| !self.allows(pair[0], pair[1])) {
+            Some(pair) => Err(InvalidGap {
+                from: pair[0],
+                to: pair[1],
+                gaps: self.gaps.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// How often each difference between neighbours in the chain occurs.
+/// Adapters below the outlet give a negative difference.
+pub fn histogram(chain: &[u32]) -> BTreeMap<i64, usize> {
+    let mut histogram = BTreeMap::new();
+    for pair in chain.windows(2) {
+        *histogram.entry(pair[1] as i64 - pair[0] as i64).or_insert(0) += 1;
+    }
+
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::parse_adapters;
+
+    #[test]
+    fn test_example() {
+        let rules = Rules::default();
+        let chain = rules.chain(&parse_adapters(include_str!("small_example_day10.txt"))).unwrap();
+
+        assert_eq!(rules.validate(&chain), Ok(()));
+        assert_eq!(histogram(&chain).into_iter().collect::<Vec<_>>(), vec![(1, 7), (3, 5)]);
+    }
+
+    #[test]
+    fn test_invalid_gaps() {
+        let rules = Rules::default();
+
+        let error = rules.validate(&rules.chain(&[1, 2, 6]).unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), "gap of 4 jolts from 2 to 6 is outside 1..=3");
+
+        let error = rules.validate(&rules.chain(&[1, 1, 2]).unwrap()).unwrap_err();
+        assert_eq!((error.from, error.to), (1, 1));
+
+        let rules = Rules { outlet: 5, device_offset: 1, gaps: 1..=2 };
+        assert_eq!(rules.chain(&[7, 6]).unwrap(), vec![5, 6, 7, 8]);
+
+        let chain = rules.chain(&[3, 6]).unwrap();
+        let error = rules.validate(&chain).unwrap_err();
+        assert_eq!(error.to_string(), "adapter 3 is below the outlet at 5");
+        assert_eq!(histogram(&chain).into_iter().collect::<Vec<_>>(), vec![(-2, 1), (1, 1), (3, 1)]);
+
+        let error = rules.chain(&[u32::MAX]).unwrap_err();
+        assert_eq!(error, DeviceOverflow { highest: u32::MAX, device_offset: 1 });
+        assert_eq!(error.to_string(), format!("device rated 1 jolts above {} overflows u32", u32::MAX));
+    }
+}
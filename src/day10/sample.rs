@@ -0,0 +1,85 @@
+use crate::arrangements::{successors, ways_to_device, Overflow};
+use crate::rules::Rules;
+
+/// A splitmix64 generator, so samples can be reproduced from their seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`. The modulo bias is at most `n / 2^128`.
+    pub fn below(&mut self, n: u128) -> u128 {
+        let high = self.next_u64() as u128;
+        let low = self.next_u64() as u128;
+
+        ((high << 64) | low) % n
+    }
+}
+
+/// Picks one arrangement uniformly at random, or `None` if there are none.
+/// Fails when there are too many arrangements to count.
+///
+/// Walking from the outlet, each next joltage is chosen with a probability
+/// proportional to the number of ways it can continue to the device, which
+/// makes every complete arrangement equally likely.
+pub fn sample(chain: &[u32], rules: &Rules, rng: &mut Rng) -> Result<Option<Vec<u32>>, Overflow> {
+    let to_device = ways_to_device(chain, rules)?;
+    if to_device.first().copied().unwrap_or(0) == 0 {
+        return Ok(None);
+    }
+
+    let device = chain.len() - 1;
+    let mut path = vec![chain[0]];
+    let mut i = 0;
+    while i != device {
+        let mut pick = rng.below(to_device[i]);
+        for k in successors(chain, rules, i) {
+            if pick < to_device[k] {
+                i = k;
+                break;
+            }
+            pick -= to_device[k];
+        }
+
+        path.push(chain[i]);
+    }
+
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use crate::arrangements::Arrangements;
+    use crate::parse_adapters;
+
+    #[test]
+    fn test_uniform() {
+        let rules = Rules::default();
+        let chain = rules.chain(&parse_adapters(include_str!("small_example_day10.txt"))).unwrap();
+
+        let mut counts: HashMap<Vec<u32>, usize> = HashMap::new();
+        let mut rng = Rng::new(7);
+        for _ in 0..8000 {
+            *counts.entry(sample(&chain, &rules, &mut rng).unwrap().unwrap()).or_insert(0) += 1;
+        }
+
+        // NOTE: Each of the 8 arrangements should come up about 1000 times.
+        assert_eq!(counts.len(), 8);
+        assert!(counts.values().all(|n| (850..1150).contains(n)));
+        assert!(Arrangements::new(&chain, &rules).all(|arrangement| counts.contains_key(&arrangement)));
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::Seats;
+
+const DIRECTIONS: [(isize, isize); 8] = [(-1, 0), (-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1)];
+
+/// Which seats a seat looks at when deciding what happens next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Neighbourhood {
+    /// The eight seats around it.
+    Adjacent,
+    /// The first seat in each of the eight directions. `floors` limits how
+    /// many floor cells a line may pass over, `None` means any number.
+    LineOfSight { floors: Option<usize> },
+    /// The cells at these `(x, y)` offsets.
+    Offsets(Vec<(isize, isize)>),
+}
+
+impl Neighbourhood {
+    /// The positions of the cells that the seat at `(x, y)` looks at.
+    pub fn neighbours<'a>(&'a self, seats: &'a Seats, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let (offsets, sight, floors) = match self {
+            Neighbourhood::Adjacent => (&DIRECTIONS[..], false, None),
+            Neighbourhood::LineOfSight { floors } => (&DIRECTIONS[..], true, *floors),
+            Neighbourhood::Offsets(offsets) => (&offsets[..], false, None),
+        };
+
+        offsets.iter().filter_map(move |offset| {
+            if sight {
+                first_seat(seats, x, y, *offset, floors)
+            } else {
+                cell(seats, x, y, *offset)
+            }
+        })
+    }
+
+    /// The number of occupied seats that the seat at `(x, y)` sees.
+    pub fn occupied(&self, seats: &Seats, x: usize, y: usize) -> usize {
+        self.neighbours(seats, x, y)
+            .filter(|(x, y)| seats[*y][*x] == '#')
+            .count()
+    }
+}
+
+/// The position at `offset` from `(x, y)`, if it is on the grid.
+fn cell(seats: &Seats, x: usize, y: usize, offset: (isize, isize)) -> Option<(usize, usize)> {
+    let x = x as isize + offset.0;
+    let y = y as isize + offset.1;
+    if x < 0 || y < 0 {
+        return None;
+    }
+
+    let (x, y) = (x as usize, y as usize);
+    seats.get(y)?.get(x)?;
+
+    Some((x, y))
+}
+
+/// The position of the first seat in `direction`, skipping at most `floors` floor cells.
+fn first_seat(
+    seats: &Seats,
+    x: usize,
+    y: usize,
+    direction: (isize, isize),
+    floors: Option<usize>,
+) -> Option<(usize, usize)> {
+    let mut distance = 1;
+    loop {
+        let (cx, cy) = cell(seats, x, y, (direction.0 * distance, direction.1 * distance))?;
+        if seats[cy][cx] != '.' {
+            return Some((cx, cy));
+        }
+
+        // NOTE: Every cell passed so far was floor, this one included.
+        if floors.is_some_and(|floors| distance as usize > floors) {
+            return None;
+        }
+        distance += 1;
+    }
+}
+
+/// How a cell changes given how many occupied seats it sees.
+pub trait Rule {
+    fn next(&self, cell: char, occupied: usize) -> char;
+}
+
+/// The seating rules: an empty seat is taken when nobody is visible, and an
+/// occupied seat is left once `tolerance` or more others are visible.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Seating {
+    pub tolerance: usize,
+}
+
+impl Rule for Seating {
+    fn next(&self, cell: char, occupied: usize) -> char {
+        match cell {
+            'L' if occupied == 0 => '#',
+            '#' if occupied >= self.tolerance => 'L',
+            c => c,
+        }
+    }
+}
+
+pub struct Automaton<R: Rule> {
+    pub neighbourhood: Neighbourhood,
+    pub rule: R,
+}
+
+impl<R: Rule> Automaton<R> {
+    /// Computes the next generation.
+    pub fn step(&self, seats: &Seats) -> Seats {
+        seats
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, c)| match c {
+                        '.' => '.',
+                        c => self.rule.next(*c, self.neighbourhood.occupied(seats, x, y)),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Automaton<Seating> {
+    /// Part 1: adjacent seats, leaving at 4.
+    pub fn part1() -> Automaton<Seating> {
+        Automaton {
+            neighbourhood: Neighbourhood::Adjacent,
+            rule: Seating { tolerance: 4 },
+        }
+    }
+
+    /// Part 2: the first seat in each direction, leaving at 5.
+    pub fn part2() -> Automaton<Seating> {
+        Automaton {
+            neighbourhood: Neighbourhood::LineOfSight { floors: None },
+            rule: Seating { tolerance: 5 },
+        }
+    }
+}
+
+/// The straightforward way to run an automaton: a fresh grid of characters
+/// every generation. Much slower than `Simulation`, but easy to check.
+pub struct Reference<R: Rule> {
+    pub automaton: Automaton<R>,
+    pub seats: Seats,
+}
+
+impl<R: Rule> Reference<R> {
+    pub fn new(seats: &Seats, automaton: Automaton<R>) -> Reference<R> {
+        Reference { automaton, seats: seats.clone() }
+    }
+
+    /// Computes the next generation, returning whether any cell changed.
+    pub fn step(&mut self) -> bool {
+        let next = self.automaton.step(&self.seats);
+        let changed = next != self.seats;
+        self.seats = next;
+
+        changed
+    }
+
+    pub fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.seats.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{count_occupied_seats, parse_seating};
+
+    fn settle<R: Rule>(automaton: &Automaton<R>, seats: Seats) -> Seats {
+        let mut prev = seats;
+        loop {
+            let next = automaton.step(&prev);
+            if next == prev {
+                return prev;
+            }
+            prev = next;
+        }
+    }
+
+    #[test]
+    fn test_example() {
+        let seats = parse_seating(include_str!("example_day11.txt"));
+
+        assert_eq!(count_occupied_seats(&settle(&Automaton::part1(), seats.clone())), 37);
+        assert_eq!(count_occupied_seats(&settle(&Automaton::part2(), seats)), 26);
+    }
+
+    #[test]
+    fn test_neighbourhoods() {
+        let seats = parse_seating("#..L.#\n......\n#.....");
+
+        assert_eq!(Neighbourhood::Adjacent.occupied(&seats, 1, 1), 2);
+        assert_eq!(Neighbourhood::LineOfSight { floors: None }.occupied(&seats, 3, 0), 2);
+        assert_eq!(Neighbourhood::LineOfSight { floors: Some(2) }.occupied(&seats, 3, 0), 2);
+        assert_eq!(Neighbourhood::LineOfSight { floors: Some(1) }.occupied(&seats, 3, 0), 1);
+        assert_eq!(Neighbourhood::LineOfSight { floors: None }.occupied(&seats, 0, 0), 1);
+        assert_eq!(Neighbourhood::LineOfSight { floors: Some(1) }.occupied(&seats, 0, 0), 1);
+        assert_eq!(Neighbourhood::LineOfSight { floors: Some(0) }.occupied(&seats, 0, 0), 0);
+        assert_eq!(
+            Neighbourhood::LineOfSight { floors: Some(0) }.occupied(&seats, 0, 0),
+            Neighbourhood::Adjacent.occupied(&seats, 0, 0)
+        );
+        assert_eq!(Neighbourhood::LineOfSight { floors: None }.occupied(&seats, 5, 2), 2);
+        assert_eq!(Neighbourhood::Offsets(vec![(2, 0), (-3, 0)]).occupied(&seats, 3, 0), 2);
+    }
+}
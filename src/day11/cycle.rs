@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::automaton::{Reference, Rule};
+use crate::simulation::{Cell, Simulation};
+use crate::Seats;
+
+/// A simulation that can be run generation by generation.
+pub trait Generations {
+    /// A full copy of the current generation, to rule out hash collisions.
+    type Snapshot: PartialEq;
+
+    /// Computes the next generation, returning whether any cell changed.
+    fn step(&mut self) -> bool;
+    fn hash(&self) -> u64;
+    fn snapshot(&self) -> Self::Snapshot;
+    fn seats(&self) -> Seats;
+    fn occupied(&self) -> usize;
+}
+
+impl<R: Rule> Generations for Simulation<R> {
+    type Snapshot = Vec<Cell>;
+
+    fn step(&mut self) -> bool {
+        Simulation::step(self)
+    }
+
+    fn hash(&self) -> u64 {
+        Simulation::hash(self)
+    }
+
+    fn snapshot(&self) -> Vec<Cell> {
+        self.cells().to_vec()
+    }
+
+    fn seats(&self) -> Seats {
+        Simulation::seats(self)
+    }
+
+    fn occupied(&self) -> usize {
+        Simulation::occupied(self)
+    }
+}
+
+impl<R: Rule> Generations for Reference<R> {
+    type Snapshot = Seats;
+
+    fn step(&mut self) -> bool {
+        Reference::step(self)
+    }
+
+    fn hash(&self) -> u64 {
+        Reference::hash(self)
+    }
+
+    fn snapshot(&self) -> Seats {
+        self.seats.clone()
+    }
+
+    fn seats(&self) -> Seats {
+        self.seats.clone()
+    }
+
+    fn occupied(&self) -> usize {
+        crate::count_occupied_seats(&self.seats) as usize
+    }
+}
+
+/// How a run ended. Generations are counted from 0, the initial layout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// Generation `generation` is the first that the next step leaves unchanged.
+    Settled { generation: usize },
+    /// Generation `start` repeats every `period` generations. A period of 1
+    /// would be a settled layout, so this is always at least 2.
+    Cycle { start: usize, period: usize },
+    /// Neither happened before generation `generation`, the cap.
+    Capped { generation: usize },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Settled { generation } => write!(f, "settled after {} generations", generation),
+            Outcome::Cycle { start, period } => {
+                write!(f, "repeats every {} generations from generation {}", period, start)
+            }
+            Outcome::Capped { generation } => write!(f, "still changing after {} generations", generation),
+        }
+    }
+}
+
+/// Steps `simulation` until it settles, repeats a generation seen before, or
+/// reaches generation `cap`. `on_generation` sees every generation after the
+/// initial one, up to the one where the run stops.
+///
+/// Every generation is remembered by its hash. When a hash comes round again
+/// the simulation is run for one more period and compared against a snapshot,
+/// so a hash collision can't be mistaken for a cycle. The cap applies to
+/// those generations as well.
+pub fn run<G, F>(simulation: &mut G, cap: Option<usize>, mut on_generation: F) -> Outcome
+where
+    G: Generations,
+    F: FnMut(usize, &G),
+{
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    let mut generation = 0;
+
+    loop {
+        if let Some(&start) = seen.get(&simulation.hash()) {
+            let period = generation - start;
+            let snapshot = simulation.snapshot();
+            for _ in 0..period {
+                if cap == Some(generation) {
+                    return Outcome::Capped { generation };
+                }
+
+                if !simulation.step() {
+                    return Outcome::Settled { generation };
+                }
+                generation += 1;
+                on_generation(generation, simulation);
+            }
+
+            if simulation.snapshot() == snapshot {
+                return Outcome::Cycle { start, period };
+            }
+        }
+        seen.insert(simulation.hash(), generation);
+
+        if cap == Some(generation) {
+            return Outcome::Capped { generation };
+        }
+
+        if !simulation.step() {
+            return Outcome::Settled { generation };
+        }
+        generation += 1;
+        on_generation(generation, simulation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::{Automaton, Neighbourhood, Seating};
+    use crate::parse_seating;
+
+    #[test]
+    fn test_settles() {
+        let seats = parse_seating(include_str!("example_day11.txt"));
+
+        let mut simulation = Simulation::new(&seats, Automaton::part1(), false);
+        assert_eq!(run(&mut simulation, None, |_, _| ()), Outcome::Settled { generation: 5 });
+        assert_eq!(simulation.occupied(), 37);
+
+        let mut reference = Reference::new(&seats, Automaton::part2());
+        assert_eq!(run(&mut reference, None, |_, _| ()), Outcome::Settled { generation: 6 });
+        assert_eq!(reference.occupied(), 26);
+
+        let mut simulation = Simulation::new(&seats, Automaton::part1(), true);
+        assert_eq!(run(&mut simulation, Some(3), |_, _| ()), Outcome::Capped { generation: 3 });
+    }
+
+    #[test]
+    fn test_cycle() {
+        // NOTE: With a tolerance of 1 the two seats fill up and empty together.
+        let seats = parse_seating("LL");
+        let automaton = || Automaton {
+            neighbourhood: Neighbourhood::Adjacent,
+            rule: Seating { tolerance: 1 },
+        };
+
+        let mut generations = vec![];
+        let mut simulation = Simulation::new(&seats, automaton(), false);
+        let outcome = run(&mut simulation, Some(100), |generation, simulation| {
+            generations.push((generation, simulation.occupied()))
+        });
+        assert_eq!(outcome, Outcome::Cycle { start: 0, period: 2 });
+        assert_eq!(generations, vec![(1, 2), (2, 0), (3, 2), (4, 0)]);
+
+        let mut reference = Reference::new(&seats, automaton());
+        assert_eq!(run(&mut reference, None, |_, _| ()), Outcome::Cycle { start: 0, period: 2 });
+
+        // NOTE: Generation 2 repeats generation 0, and the cap comes before
+        // generation 4 confirms it.
+        let mut generations = vec![];
+        let mut simulation = Simulation::new(&seats, automaton(), true);
+        let outcome = run(&mut simulation, Some(3), |generation, _| generations.push(generation));
+        assert_eq!(outcome, Outcome::Capped { generation: 3 });
+        assert_eq!(generations, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_late_cycle() {
+        // NOTE: It takes a generation for all three seats to empty out
+        // before they start blinking together.
+        let seats = parse_seating("##L");
+        let automaton = Automaton {
+            neighbourhood: Neighbourhood::Adjacent,
+            rule: Seating { tolerance: 1 },
+        };
+
+        let mut simulation = Simulation::new(&seats, automaton, true);
+        assert_eq!(run(&mut simulation, None, |_, _| ()), Outcome::Cycle { start: 1, period: 2 });
+    }
+}
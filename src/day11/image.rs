@@ -0,0 +1,292 @@
+use std::io::{self, Write};
+
+use crate::{pad_seating, Seats};
+
+/// The colours of floor, empty seats and occupied seats.
+const PALETTE: [[u8; 3]; 3] = [[0x20, 0x20, 0x20], [0x3c, 0xb0, 0x4c], [0xd0, 0x30, 0x30]];
+
+fn colour_index(c: char) -> u8 {
+    match c {
+        'L' => 1,
+        '#' => 2,
+        _ => 0,
+    }
+}
+
+/// A picture of the seats with every cell drawn as a `scale` by `scale`
+/// square, as one palette index per pixel. Short rows are padded with floor
+/// the same way the simulation pads them.
+pub struct Picture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Picture {
+    pub fn new(seats: &Seats, scale: usize) -> Picture {
+        let seats = &pad_seating(seats);
+        let width = seats.first().map_or(0, |row| row.len()) * scale;
+        let height = seats.len() * scale;
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in seats {
+            let line: Vec<u8> = row
+                .iter()
+                .flat_map(|c| std::iter::repeat_n(colour_index(*c), scale))
+                .collect();
+            for _ in 0..scale {
+                pixels.extend_from_slice(&line);
+            }
+        }
+
+        Picture { width, height, pixels }
+    }
+
+    fn rgb(&self) -> impl Iterator<Item = u8> + '_ {
+        self.pixels.iter().flat_map(|i| PALETTE[*i as usize].iter().copied())
+    }
+}
+
+/// Images need at least one pixel in each direction.
+fn check_size(width: usize, height: usize) -> io::Result<()> {
+    if width == 0 || height == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}x{} is too small for an image", width, height),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Writes a binary PPM (P6) image.
+pub fn write_ppm<W: Write>(mut output: W, picture: &Picture) -> io::Result<()> {
+    check_size(picture.width, picture.height)?;
+    write!(output, "P6\n{} {}\n255\n", picture.width, picture.height)?;
+    output.write_all(&picture.rgb().collect::<Vec<u8>>())
+}
+
+/// Writes an RGB PNG image. The image data is stored without compression,
+/// which every decoder reads and which needs nothing beyond a CRC and an
+/// Adler-32 checksum.
+pub fn write_png<W: Write>(mut output: W, picture: &Picture) -> io::Result<()> {
+    check_size(picture.width, picture.height)?;
+    output.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = vec![];
+    header.extend_from_slice(&(picture.width as u32).to_be_bytes());
+    header.extend_from_slice(&(picture.height as u32).to_be_bytes());
+    // NOTE: 8 bits per channel, RGB, default compression, filtering and no interlacing.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut output, b"IHDR", &header)?;
+
+    // NOTE: Every scanline starts with filter type 0, none.
+    let rgb: Vec<u8> = picture.rgb().collect();
+    let mut raw = Vec::with_capacity(picture.height * (picture.width * 3 + 1));
+    for line in rgb.chunks(picture.width * 3).take(picture.height) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+
+    write_chunk(&mut output, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(&mut output, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(output: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    output.write_all(&(data.len() as u32).to_be_bytes())?;
+    output.write_all(kind)?;
+    output.write_all(data)?;
+
+    let crc = crc32(kind.iter().chain(data));
+    output.write_all(&crc.to_be_bytes())
+}
+
+/// A zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+/// The LZW code size of the frames. Pixels only use the first few palette
+/// entries, but a minimum code size of 7 makes every code exactly one byte.
+const GIF_CODE_SIZE: u8 = 7;
+const GIF_CLEAR: u8 = 1 << GIF_CODE_SIZE;
+const GIF_END: u8 = GIF_CLEAR + 1;
+/// How many pixels go between clear codes. The decoder adds a table entry
+/// for every code, and clearing well before the table would need 9 bit
+/// codes keeps every code one byte.
+const GIF_RUN: usize = 100;
+
+/// Writes an animated GIF that loops forever, one frame at a time.
+pub struct Gif<W: Write> {
+    output: W,
+    width: usize,
+    height: usize,
+}
+
+impl<W: Write> Gif<W> {
+    pub fn new(mut output: W, width: usize, height: usize) -> io::Result<Gif<W>> {
+        check_size(width, height)?;
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}x{} is too large for a GIF", width, height),
+            ));
+        }
+
+        output.write_all(b"GIF89a")?;
+        output.write_all(&(width as u16).to_le_bytes())?;
+        output.write_all(&(height as u16).to_le_bytes())?;
+        // NOTE: A global colour table of 2^(6 + 1) = 128 entries to match the code size.
+        output.write_all(&[0xf6, 0, 0])?;
+        for i in 0..128 {
+            output.write_all(PALETTE.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+
+        output.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        Ok(Gif { output, width, height })
+    }
+
+    /// Adds a frame shown for `delay` hundredths of a second.
+    pub fn frame(&mut self, picture: &Picture, delay: u16) -> io::Result<()> {
+        if (picture.width, picture.height) != (self.width, self.height) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "frames must all have the same size"));
+        }
+
+        self.output.write_all(&[0x21, 0xf9, 4, 0])?;
+        self.output.write_all(&delay.to_le_bytes())?;
+        self.output.write_all(&[0, 0])?;
+
+        self.output.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.output.write_all(&(self.width as u16).to_le_bytes())?;
+        self.output.write_all(&(self.height as u16).to_le_bytes())?;
+        self.output.write_all(&[0, GIF_CODE_SIZE])?;
+
+        let mut codes = Vec::with_capacity(picture.pixels.len() + picture.pixels.len() / GIF_RUN + 2);
+        for run in picture.pixels.chunks(GIF_RUN) {
+            codes.push(GIF_CLEAR);
+            codes.extend_from_slice(run);
+        }
+        codes.push(GIF_END);
+
+        for block in codes.chunks(255) {
+            self.output.write_all(&[block.len() as u8])?;
+            self.output.write_all(block)?;
+        }
+        self.output.write_all(&[0])
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.output.write_all(&[0x3b])?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::parse_seating;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"123456789".iter()), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND".iter()), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_picture() {
+        let picture = Picture::new(&parse_seating("L.\n#L"), 2);
+        assert_eq!((picture.width, picture.height), (4, 4));
+        assert_eq!(picture.pixels, vec![1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 1, 1, 2, 2, 1, 1]);
+
+        let mut ppm = vec![];
+        write_ppm(&mut ppm, &picture).unwrap();
+        assert!(ppm.starts_with(b"P6\n4 4\n255\n"));
+        assert_eq!(ppm.len(), 11 + 4 * 4 * 3);
+
+        let mut png = vec![];
+        write_png(&mut png, &picture).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"));
+        assert!(png.ends_with(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82"));
+
+        // NOTE: The first row is padded to the width of the second.
+        let picture = Picture::new(&parse_seating("L\n#L"), 1);
+        assert_eq!((picture.width, picture.height), (2, 2));
+        assert_eq!(picture.pixels, vec![1, 0, 2, 1]);
+
+        for picture in [Picture::new(&parse_seating(""), 2), Picture::new(&parse_seating("L#"), 0)].iter() {
+            assert!(write_ppm(vec![], picture).is_err());
+            assert!(write_png(vec![], picture).is_err());
+        }
+    }
+
+    #[test]
+    fn test_gif() {
+        let seats = parse_seating(include_str!("example_day11.txt"));
+        let picture = Picture::new(&seats, 3);
+
+        let mut gif = Gif::new(vec![], picture.width, picture.height).unwrap();
+        gif.frame(&picture, 10).unwrap();
+        let gif = gif.finish().unwrap();
+
+        assert!(gif.starts_with(b"GIF89a\x1e\x00\x1e\x00"));
+        assert_eq!(gif.last(), Some(&0x3b));
+
+        // NOTE: Every code is one byte, so dropping the clear codes from the
+        // image data gives back the pixels.
+        let start = 6 + 7 + 128 * 3 + 19 + 8 + 10 + 1;
+        let mut data = vec![];
+        let mut i = start;
+        while gif[i] != 0 {
+            data.extend_from_slice(&gif[i + 1..i + 1 + gif[i] as usize]);
+            i += 1 + gif[i] as usize;
+        }
+
+        assert_eq!(data.pop(), Some(GIF_END));
+        let pixels: Vec<u8> = data.into_iter().filter(|code| *code != GIF_CLEAR).collect();
+        assert_eq!(pixels, picture.pixels);
+
+        assert!(Gif::new(vec![], 70_000, 1).is_err());
+        assert!(Gif::new(vec![], 0, 1).is_err());
+    }
+}
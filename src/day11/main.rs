@@ -2,6 +2,19 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::io::{self};
 use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+mod automaton;
+mod cycle;
+mod image;
+mod simulation;
+mod visualise;
+
+use automaton::{Automaton, Neighbourhood, Reference};
+use cycle::Generations;
+use simulation::Simulation;
+use visualise::{FrameFormat, Settings, Visualiser};
 
 type Seats = Vec<Vec<char>>;
 
@@ -10,276 +23,219 @@ enum Part {
     Part2
 }
 
+struct Options {
+    part: Part,
+    input: String,
+    neighbourhood: Option<Neighbourhood>,
+    tolerance: Option<usize>,
+    floors: Option<usize>,
+    incremental: bool,
+    reference: bool,
+    cap: Option<usize>,
+    visuals: Settings,
+}
+
 fn main() -> io::Result<()> {
     let args: Vec<String> = env::args().collect();
-    let mut part = Part::Part1;
-    if args.len() > 1 {
-        let arg = &args[1];
-        match arg.as_str() {
-            "1" => part = Part::Part1,
-            "2" => part = Part::Part2,
-            _ => ()
-        }
-    }
+    let options = parse_options(&args);
 
-    let mut f = File::open("src/day11/input_day11.txt")?;
+    let mut f = File::open(&options.input)?;
 
     let mut s = String::new();
     f.read_to_string(&mut s)?;
 
     let seats = parse_seating(&s);
 
-    match part {
-        Part::Part1 => {
-            print_seating(&seats);
-
-            let height = seats.len();
-            let width = seats.first().unwrap().len();
-            let mut prev = seats;
-            loop {
-                let next = apply_rules_part1(&prev, width, height);
-
-                if compare(&prev, &next, (width, height)) {
-                    break;
-                }
-
-                prev = next;
-            }
-
-            let occupied_seats = count_occupied_seats(&prev);
-            println!("occupied seats: {}", occupied_seats);
-        }
-        Part::Part2 => {
-            print_seating(&seats);
-
-            let height = seats.len();
-            let width = seats.first().unwrap().len();
-            let mut prev = seats;
-            loop {
-                let next = apply_rules_part2(&prev, width, height);
-
-                print_seating(&next);
-
-                if compare(&prev, &next, (width, height)) {
-                    break;
-                }
-
-                prev = next;
-            }
-
-            let occupied_seats = count_occupied_seats(&prev);
-            println!("occupied seats: {}", occupied_seats);
-        }
+    let mut automaton = match options.part {
+        Part::Part1 => Automaton::part1(),
+        Part::Part2 => Automaton::part2(),
+    };
+    if let Some(neighbourhood) = options.neighbourhood.clone() {
+        automaton.neighbourhood = neighbourhood;
+    }
+    if let Some(tolerance) = options.tolerance {
+        automaton.rule.tolerance = tolerance;
+    }
+    if let (Some(floors), Neighbourhood::LineOfSight { .. }) = (options.floors, &automaton.neighbourhood) {
+        automaton.neighbourhood = Neighbourhood::LineOfSight { floors: Some(floors) };
     }
 
-    Ok(())
-}
-
-fn parse_seating(s: &String) -> Seats {
-    let mut seats: Seats = vec![];
-
-    for line in s.lines() {
-        let mut l = vec![];
-        let line: String = line.split_whitespace().collect();
-
-        for c in line.chars() {
-            l.push(c);
-        }
-
-        seats.push(l);
+    let mut visualiser = Visualiser::new(options.visuals.clone())?;
+    if options.reference {
+        simulate(&mut Reference::new(&seats, automaton), options.cap, &mut visualiser)?;
+    } else {
+        simulate(&mut Simulation::new(&seats, automaton, options.incremental), options.cap, &mut visualiser)?;
     }
 
-    seats
+    visualiser.finish()
 }
 
-fn count_occupied_seats(seats: &Seats) -> u32 {
-    let mut occupied_seats = 0;
-
-    for line in seats {
-        for c in line {
-            if *c == '#' {
-                occupied_seats += 1;
-            }
-        }
+fn simulate<G: Generations>(simulation: &mut G, cap: Option<usize>, visualiser: &mut Visualiser) -> io::Result<()> {
+    // NOTE: The run can't be stopped from inside, so after a failed write the
+    // remaining generations are skipped and the error is reported at the end.
+    let mut result = Ok(());
+    if visualiser.is_active() {
+        result = visualiser.show(0, &simulation.seats());
     }
 
-    occupied_seats
-}
-
-fn print_seating(seats: &Seats) {
-    for line in seats {
-        for c in line {
-            print!("{}", c);
+    let outcome = cycle::run(simulation, cap, |generation, simulation| {
+        if result.is_ok() && visualiser.is_active() {
+            result = visualiser.show(generation, &simulation.seats());
         }
+    });
 
-        print!("\n");
-    }
+    println!("occupied seats: {}", simulation.occupied());
+    println!("{}", outcome);
 
-    print!("\n");
+    result
 }
 
-fn compare(prev: &Seats, current: &Seats, size: (usize, usize)) -> bool {
-    let (width, height) = size;
-
-    for i in 0..height {
-        for j in 0..width {
-            if prev[i][j] != current[i][j] {
-                return false;
+/// `day11 [1|2] [--input <file>] [--neighbourhood adjacent|sight]
+/// [--tolerance <n>] [--floors <n>] [--offsets <dx,dy;dx,dy;...>]
+/// [--incremental|--reference] [--cap <n>] [--print] [--animate] [--delay <ms>]
+/// [--frames <dir>] [--frame-format ppm|png] [--gif <file>] [--csv <file>] [--scale <n>]`
+///
+/// Part 1 defaults to adjacent seats with a tolerance of 4 and part 2 to line
+/// of sight with a tolerance of 5. `--floors` limits how many floor cells a
+/// line of sight may pass over, and `--offsets` looks at
+/// exactly the cells at those offsets instead. `--incremental` only
+/// re-evaluates seats whose neighbourhood changed in the previous step, and
+/// `--reference` runs the plain grid-copying `Automaton::step` instead.
+///
+/// Runs stop when the layout settles or a generation repeats, or at
+/// generation `--cap` if that comes first.
+///
+/// Nothing but the result is printed unless asked for: `--print` prints every
+/// generation, `--animate` redraws them in the terminal `--delay` apart,
+/// `--frames` and `--gif` draw them as images with `--scale` pixels per
+/// cell, and `--csv` records how many seats are occupied in each.
+fn parse_options(args: &[String]) -> Options {
+    let mut part = Part::Part1;
+    let mut input = String::from("src/day11/input_day11.txt");
+    let mut neighbourhood = None;
+    let mut tolerance = None;
+    let mut floors = None;
+    let mut incremental = false;
+    let mut reference = false;
+    let mut cap = None;
+    let mut visuals = Settings::default();
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "1" => part = Part::Part1,
+            "2" => part = Part::Part2,
+            "--input" => {
+                if let Some(path) = args.next() {
+                    input = path.clone();
+                }
             }
-        }
-    }
-
-    true
-}
-
-fn apply_rules_part1(seats: &Seats, width: usize, height: usize) -> Seats {
-    let mut next: Seats = vec![];
-    next.reserve(height);
-
-    for i in 0..height {
-        let mut line = vec![];
-        line.resize(width, '.');
-        next.push(line);
-
-        for j in 0..width {
-            let c = seats[i][j];
-            match c {
-                '.' => next[i][j] = '.',
-                'L' => {
-                    let occupied_seats = adjacent_occupied_seats(&seats, (width, height), (i, j));
-                    if occupied_seats == 0 {
-                        next[i][j] = '#';
-                    } else {
-                        next[i][j] = 'L';
-                    }
+            "--neighbourhood" => match args.next().map(|name| name.as_str()) {
+                Some("adjacent") => neighbourhood = Some(Neighbourhood::Adjacent),
+                Some("sight") => neighbourhood = Some(Neighbourhood::LineOfSight { floors: None }),
+                _ => (),
+            },
+            "--tolerance" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    tolerance = Some(n);
                 }
-                '#' => {
-                    let occupied_seats = adjacent_occupied_seats(&seats, (width, height), (i, j));
-                    if occupied_seats >= 4 {
-                        next[i][j] = 'L';
-                    } else {
-                        next[i][j] = '#';
-                    }
+            }
+            "--floors" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    floors = Some(n);
                 }
-                _ => (),
             }
-        }
-    }
-
-    next
-}
-
-fn apply_rules_part2(seats: &Seats, width: usize, height: usize) -> Seats {
-    let mut next: Seats = vec![];
-    next.reserve(height);
-
-    for y in 0..height {
-        let mut line = vec![];
-        line.resize(width, '.');
-        next.push(line);
-
-        for x in 0..width {
-            let c = seats[y][x];
-            match c {
-                '.' => next[y][x] = '.',
-                'L' => {
-                    let occupied_seats = check_visible_seats(&seats, (width, height), (x, y));
-                    if occupied_seats == 0 {
-                        next[y][x] = '#';
-                    } else {
-                        next[y][x] = 'L';
-                    }
+            "--incremental" => incremental = true,
+            "--reference" => reference = true,
+            "--print" => visuals.print = true,
+            "--animate" => visuals.animate = true,
+            "--delay" => {
+                if let Some(ms) = args.next().and_then(|ms| ms.parse().ok()) {
+                    visuals.delay = Duration::from_millis(ms);
+                }
+            }
+            "--frames" => visuals.frames = args.next().map(PathBuf::from),
+            "--frame-format" => {
+                if let Some(format) = args.next().and_then(|name| FrameFormat::from_name(name)) {
+                    visuals.format = format;
                 }
-                '#' => {
-                    let occupied_seats = check_visible_seats(&seats, (width, height), (x, y));
-                    if occupied_seats >= 5 {
-                        next[y][x] = 'L';
-                    } else {
-                        next[y][x] = '#';
+            }
+            "--gif" => visuals.gif = args.next().map(PathBuf::from),
+            "--csv" => visuals.csv = args.next().map(PathBuf::from),
+            "--scale" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    if n == 0 {
+                        eprintln!("--scale must be at least 1");
+                        std::process::exit(1);
                     }
+                    visuals.scale = n;
+                }
+            }
+            "--cap" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    cap = Some(n);
                 }
-                _ => (),
             }
+            "--offsets" => {
+                if let Some(offsets) = args.next().and_then(|offsets| parse_offsets(offsets)) {
+                    neighbourhood = Some(Neighbourhood::Offsets(offsets));
+                }
+            }
+            _ => (),
         }
     }
 
-    next
+    Options { part, input, neighbourhood, tolerance, floors, incremental, reference, cap, visuals }
 }
 
-fn adjacent_occupied_seats(seats: &Seats, size: (usize, usize), coord: (usize, usize)) -> usize {
-    let (width, height) = size;
-    let (x, y) = coord;
-
-    let width = width as isize;
-    let height = height as isize;
-    let x = x as isize;
-    let y = y as isize;
-
-    let mut occupied_seats = 0;
-    let x_start = x - 1;
-    let x_end = x + 2;
-    let y_start = y - 1;
-    let y_end = y + 2;
-
-    for i in x_start..x_end {
-        for j in y_start..y_end {
-            if i < 0 || j < 0 {
-                continue;
-            }
-
-            if i >= height || j >= width {
-                continue;
+/// Parses offsets written as `dx,dy;dx,dy;...`.
+fn parse_offsets(s: &str) -> Option<Vec<(isize, isize)>> {
+    s.split(';')
+        .filter(|offset| !offset.trim().is_empty())
+        .map(|offset| {
+            let mut parts = offset.split(',').map(|n| n.trim().parse().ok());
+            match (parts.next()?, parts.next()?, parts.next()) {
+                (Some(dx), Some(dy), None) => Some((dx, dy)),
+                _ => None,
             }
+        })
+        .collect()
+}
 
-            let x_coord = i as usize;
-            let y_coord = j as usize;
+fn parse_seating(s: &str) -> Seats {
+    let mut seats: Seats = vec![];
 
-            if coord == (x_coord, y_coord) {
-                continue;
-            }
+    for line in s.lines() {
+        let mut l = vec![];
+        let line: String = line.split_whitespace().collect();
 
-            if seats[x_coord][y_coord] == '#' {
-                occupied_seats += 1;
-            }
+        for c in line.chars() {
+            l.push(c);
         }
+
+        seats.push(l);
     }
 
-    occupied_seats
+    seats
 }
 
-fn check_visible_seats(seats: &Seats, size: (usize, usize), coord: (usize, usize)) -> usize {
-    let mut occupied_seats = 0;
-
-    let directions: [(isize, isize); 8] = [(-1, 0), (-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1)];
+/// The seats with every row padded with floor to the length of the longest.
+fn pad_seating(seats: &Seats) -> Seats {
+    let width = seats.iter().map(|row| row.len()).max().unwrap_or(0);
 
-    for direction in directions.iter() {
-        let mut x: isize = coord.0 as isize;
-        let mut y: isize = coord.1 as isize;
-
-        let width = size.0 as isize;
-        let height = size.1 as isize;
-        let (x_dir, y_dir) = direction;
-
-        loop {
-            x += x_dir;
-            y += y_dir;
-
-            if x < 0 || x >= width {
-                break;
-            }
+    seats
+        .iter()
+        .map(|row| row.iter().copied().chain(std::iter::repeat('.')).take(width).collect())
+        .collect()
+}
 
-            if y < 0 || y >= height {
-                break;
-            }
+fn count_occupied_seats(seats: &Seats) -> u32 {
+    let mut occupied_seats = 0;
 
-            let c = seats[y as usize][x as usize];
-            match c {
-                'L' => break,
-                '#' => {
-                    occupied_seats += 1;
-                    break;
-                }
-                _ => (),
+    for line in seats {
+        for c in line {
+            if *c == '#' {
+                occupied_seats += 1;
             }
         }
     }
@@ -305,9 +261,7 @@ mod tests {
         ...#....."#);
 
         let seats = parse_seating(&s);
-        let coord = (3, 4);
-        let size = (9, 9);
-        let count = check_visible_seats(&seats, size, coord);
+        let count = Neighbourhood::LineOfSight { floors: None }.occupied(&seats, 3, 4);
 
         assert_eq!(count, 8);
     }
@@ -320,9 +274,7 @@ mod tests {
         ............."#);
 
         let seats = parse_seating(&s);
-        let coord = (1, 1);
-        let size = (13, 3);
-        let count = check_visible_seats(&seats, size, coord);
+        let count = Neighbourhood::LineOfSight { floors: None }.occupied(&seats, 1, 1);
 
         assert_eq!(count, 0);
     }
@@ -339,9 +291,7 @@ mod tests {
         .##.##."#);
 
         let seats = parse_seating(&s);
-        let coord = (3, 3);
-        let size = (7, 7);
-        let count = check_visible_seats(&seats, size, coord);
+        let count = Neighbourhood::LineOfSight { floors: None }.occupied(&seats, 3, 3);
 
         assert_eq!(count, 0);
     }
@@ -0,0 +1,375 @@
+use std::mem;
+
+use crate::automaton::{Automaton, Rule};
+use crate::{pad_seating, Seats};
+
+/// A cell of the flat grid, stored as the byte it is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Cell {
+    Floor = b'.',
+    Empty = b'L',
+    Occupied = b'#',
+}
+
+impl Cell {
+    pub fn from_char(c: char) -> Cell {
+        match c {
+            'L' => Cell::Empty,
+            '#' => Cell::Occupied,
+            _ => Cell::Floor,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        self as u8 as char
+    }
+}
+
+/// A list for every cell packed into one array: the list of cell `i` is
+/// `list[starts[i]..starts[i + 1]]`.
+struct Lists {
+    starts: Vec<u32>,
+    list: Vec<u32>,
+}
+
+impl Lists {
+    fn of(&self, i: usize) -> &[u32] {
+        &self.list[self.starts[i] as usize..self.starts[i + 1] as usize]
+    }
+}
+
+/// Runs an automaton over a flat, double-buffered grid.
+///
+/// Seats never turn into floor or back, so which seats each seat looks at
+/// is worked out once up front, line of sight included. Rather than looking
+/// at those seats every step, every cell keeps a count of its occupied
+/// neighbours, and a cell that changes updates the counts of the cells
+/// watching it. A step then writes the rule's verdict for every cell into
+/// the second buffer and swaps, collecting the cells that changed.
+///
+/// With `incremental` set, only cells whose count changed or which changed
+/// themselves in the previous step are evaluated again; nothing else can.
+pub struct Simulation<R: Rule> {
+    rule: R,
+    width: usize,
+    cells: Vec<Cell>,
+    next: Vec<Cell>,
+    /// The number of occupied neighbours of every cell.
+    counts: Vec<u16>,
+    /// For every cell, the cells that have it as a neighbour.
+    watchers: Lists,
+    incremental: bool,
+    /// The cells to evaluate in the next incremental step.
+    active: Vec<u32>,
+    /// Whether a cell is already in `pending`.
+    queued: Vec<bool>,
+    /// Scratch buffers kept between steps so stepping doesn't allocate.
+    pending: Vec<u32>,
+    changes: Vec<u32>,
+    occupied: usize,
+    /// The XOR of `key(i)` over every occupied cell `i`.
+    hash: u64,
+}
+
+impl<R: Rule> Simulation<R> {
+    pub fn new(seats: &Seats, automaton: Automaton<R>, incremental: bool) -> Simulation<R> {
+        // NOTE: The neighbours are worked out on the padded grid too, so their
+        // positions index the flat one.
+        let seats = &pad_seating(seats);
+        let width = seats.first().map_or(0, |row| row.len());
+        let cells: Vec<Cell> = seats.iter().flatten().map(|c| Cell::from_char(*c)).collect();
+
+        // NOTE: The watchers are packed in two passes, first counting how many
+        // each cell has and then filling them in, to avoid a `Vec` per cell.
+        let seat_neighbours = |i: usize| {
+            let neighbours = match cells[i] {
+                Cell::Floor => None,
+                _ => Some(automaton.neighbourhood.neighbours(seats, i % width, i / width)),
+            };
+            neighbours
+                .into_iter()
+                .flatten()
+                .map(|(x, y)| y * width + x)
+                .filter(|n| cells[*n] != Cell::Floor)
+        };
+
+        let mut counts = vec![0; cells.len()];
+        let mut starts = vec![0; cells.len() + 1];
+        for (i, count) in counts.iter_mut().enumerate() {
+            for n in seat_neighbours(i) {
+                starts[n + 1] += 1;
+                if cells[n] == Cell::Occupied {
+                    *count += 1;
+                }
+            }
+        }
+        for i in 0..cells.len() {
+            starts[i + 1] += starts[i];
+        }
+
+        let mut list = vec![0; starts[cells.len()] as usize];
+        let mut filled = starts.clone();
+        for i in 0..cells.len() {
+            for n in seat_neighbours(i) {
+                list[filled[n] as usize] = i as u32;
+                filled[n] += 1;
+            }
+        }
+
+        let active: Vec<u32> = (0..cells.len() as u32).filter(|i| cells[*i as usize] != Cell::Floor).collect();
+        let occupied = cells.iter().filter(|c| **c == Cell::Occupied).count();
+        let hash = (0..cells.len()).filter(|i| cells[*i] == Cell::Occupied).fold(0, |hash, i| hash ^ key(i));
+
+        Simulation {
+            rule: automaton.rule,
+            width,
+            next: cells.clone(),
+            queued: vec![false; cells.len()],
+            cells,
+            counts,
+            watchers: Lists { starts, list },
+            incremental,
+            active,
+            pending: vec![],
+            changes: vec![],
+            occupied,
+            hash,
+        }
+    }
+
+    /// Computes the next generation, returning whether any cell changed.
+    pub fn step(&mut self) -> bool {
+        if self.incremental {
+            self.step_active()
+        } else {
+            self.step_all()
+        }
+    }
+
+    fn next_cell(&self, i: usize) -> Cell {
+        match self.cells[i] {
+            Cell::Floor => Cell::Floor,
+            cell => Cell::from_char(self.rule.next(cell.to_char(), self.counts[i] as usize)),
+        }
+    }
+
+    fn step_all(&mut self) -> bool {
+        let mut changes = mem::take(&mut self.changes);
+        changes.clear();
+
+        for i in 0..self.cells.len() {
+            let next = self.next_cell(i);
+            if next != self.cells[i] {
+                changes.push(i as u32);
+            }
+            self.next[i] = next;
+        }
+
+        mem::swap(&mut self.cells, &mut self.next);
+        for i in &changes {
+            self.update_counts(*i as usize, self.next[*i as usize]);
+        }
+
+        let changed = !changes.is_empty();
+        self.changes = changes;
+
+        changed
+    }
+
+    fn step_active(&mut self) -> bool {
+        let active = mem::take(&mut self.active);
+        let mut changes = mem::take(&mut self.changes);
+        let mut pending = mem::take(&mut self.pending);
+
+        // NOTE: Every active cell is evaluated against the current generation
+        // before any of them is written, which is what the second buffer is for.
+        changes.clear();
+        for i in &active {
+            let i = *i as usize;
+            self.queued[i] = false;
+
+            let next = self.next_cell(i);
+            if next != self.cells[i] {
+                changes.push(i as u32);
+                self.next[i] = next;
+            }
+        }
+
+        pending.clear();
+        for i in &changes {
+            let i = *i as usize;
+            let prev = self.cells[i];
+            self.cells[i] = self.next[i];
+            self.update_counts(i, prev);
+
+            // NOTE: The rule also depends on the cell itself, so it is
+            // evaluated again even when it isn't its own neighbour.
+            for watcher in self.watchers.of(i).iter().chain(Some(&(i as u32))) {
+                if !self.queued[*watcher as usize] {
+                    self.queued[*watcher as usize] = true;
+                    pending.push(*watcher);
+                }
+            }
+        }
+
+        let changed = !changes.is_empty();
+        self.active = pending;
+        self.pending = active;
+        self.changes = changes;
+
+        changed
+    }
+
+    /// Brings the counts up to date after cell `i` changed from `prev`.
+    fn update_counts(&mut self, i: usize, prev: Cell) {
+        let next = self.cells[i];
+        let delta = match (prev == Cell::Occupied, next == Cell::Occupied) {
+            (true, false) => -1,
+            (false, true) => 1,
+            _ => return,
+        };
+
+        self.hash ^= key(i);
+        self.occupied = (self.occupied as isize + delta) as usize;
+        for watcher in self.watchers.of(i) {
+            let count = &mut self.counts[*watcher as usize];
+            *count = (*count as i16 + delta as i16) as u16;
+        }
+    }
+
+    /// The number of occupied seats.
+    pub fn occupied(&self) -> usize {
+        self.occupied
+    }
+
+    /// A hash of which seats are occupied, kept up to date as cells change.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    /// The current generation as rows of characters.
+    pub fn seats(&self) -> Seats {
+        if self.width == 0 {
+            return vec![];
+        }
+
+        self.cells
+            .chunks(self.width)
+            .map(|row| row.iter().map(|c| c.to_char()).collect())
+            .collect()
+    }
+}
+
+/// A random looking key for cell `i` (splitmix64), so that XORing the keys of
+/// the occupied cells makes a hash that can be updated one cell at a time.
+fn key(i: usize) -> u64 {
+    let mut z = (i as u64).wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::automaton::{Neighbourhood, Seating};
+    use crate::{count_occupied_seats, pad_seating, parse_seating};
+
+    /// Checks up to 100 generations against the straightforward `Automaton::step`.
+    fn check(seats: &Seats, automaton: fn() -> Automaton<Seating>, incremental: bool) -> usize {
+        let reference = automaton();
+        let mut simulation = Simulation::new(seats, automaton(), incremental);
+        let mut expected = seats.clone();
+        for _ in 0..100 {
+            let next = reference.step(&expected);
+            assert_eq!(simulation.step(), next != expected);
+            assert_eq!(simulation.seats(), next);
+            assert_eq!(simulation.occupied(), count_occupied_seats(&next) as usize);
+            expected = next;
+        }
+
+        simulation.occupied()
+    }
+
+    /// Steps until nothing changes, returning the number of steps that did.
+    fn settle<R: Rule>(simulation: &mut Simulation<R>) -> usize {
+        let mut steps = 0;
+        while simulation.step() {
+            steps += 1;
+        }
+
+        steps
+    }
+
+    #[test]
+    fn test_example() {
+        let seats = parse_seating(include_str!("example_day11.txt"));
+
+        for incremental in [false, true].iter() {
+            assert_eq!(check(&seats, Automaton::part1, *incremental), 37);
+            assert_eq!(check(&seats, Automaton::part2, *incremental), 26);
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        let seats = parse_seating(include_str!("example_day11.txt"));
+        let variants: [fn() -> Automaton<Seating>; 2] = [
+            || Automaton {
+                neighbourhood: Neighbourhood::LineOfSight { floors: Some(1) },
+                rule: Seating { tolerance: 3 },
+            },
+            || Automaton {
+                neighbourhood: Neighbourhood::Offsets(vec![(1, 0), (0, 2), (-2, -1)]),
+                rule: Seating { tolerance: 2 },
+            },
+        ];
+
+        for variant in variants.iter() {
+            assert_eq!(check(&seats, *variant, false), check(&seats, *variant, true));
+        }
+    }
+
+    #[test]
+    fn test_ragged() {
+        // NOTE: Rows longer than the first must still see the rows around them.
+        let seats = parse_seating("L\nLLLL\nLL.L\nLLLLL");
+        let padded = pad_seating(&seats);
+        assert_eq!(padded[0], vec!['L', '.', '.', '.', '.']);
+
+        for incremental in [false, true].iter() {
+            assert_eq!(check(&padded, Automaton::part1, *incremental), 5);
+
+            let mut simulation = Simulation::new(&seats, Automaton::part1(), *incremental);
+            assert_eq!(simulation.seats(), padded);
+            settle(&mut simulation);
+            assert_eq!(simulation.occupied(), 5);
+        }
+    }
+
+    #[test]
+    fn test_large() {
+        // NOTE: 30x30 copies of the example with a floor cell between them,
+        // so with adjacent seats each copy settles on its own.
+        let example = parse_seating(include_str!("example_day11.txt"));
+        let floor = vec!['.'; example[0].len()];
+        let seats: Seats = (0..30)
+            .flat_map(|_| example.iter().chain(Some(&floor)))
+            .map(|row| row.iter().chain(&['.']).cycle().take(11 * 30).copied().collect())
+            .collect();
+
+        let mut full = Simulation::new(&seats, Automaton::part1(), false);
+        let mut incremental = Simulation::new(&seats, Automaton::part1(), true);
+        assert_eq!(settle(&mut full), 5);
+        assert_eq!(settle(&mut incremental), 5);
+
+        assert_eq!(full.seats(), incremental.seats());
+        assert_eq!(full.occupied(), 37 * 900);
+    }
+}
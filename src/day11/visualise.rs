@@ -0,0 +1,248 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crate::image::{self, Gif, Picture};
+use crate::Seats;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameFormat {
+    Ppm,
+    Png,
+}
+
+impl FrameFormat {
+    pub fn from_name(name: &str) -> Option<FrameFormat> {
+        match name {
+            "ppm" => Some(FrameFormat::Ppm),
+            "png" => Some(FrameFormat::Png),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Ppm => "ppm",
+            FrameFormat::Png => "png",
+        }
+    }
+}
+
+/// Which visualisations to produce. None of them are on by default.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Print every generation as plain text.
+    pub print: bool,
+    /// Redraw every generation in place in the terminal.
+    pub animate: bool,
+    /// How long each generation is shown when animating, and in the GIF.
+    pub delay: Duration,
+    /// A directory to write one image per generation to.
+    pub frames: Option<PathBuf>,
+    pub format: FrameFormat,
+    /// A file to write an animated GIF of all generations to.
+    pub gif: Option<PathBuf>,
+    /// A file to write the number of occupied and empty seats in every generation to.
+    pub csv: Option<PathBuf>,
+    /// The size in pixels of a cell in the images.
+    pub scale: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            print: false,
+            animate: false,
+            delay: Duration::from_millis(100),
+            frames: None,
+            format: FrameFormat::Png,
+            gif: None,
+            csv: None,
+            scale: 4,
+        }
+    }
+}
+
+/// Shows or records each generation as the simulation produces it.
+pub struct Visualiser {
+    settings: Settings,
+    gif: Option<Gif<BufWriter<File>>>,
+    csv: Option<BufWriter<File>>,
+}
+
+impl Visualiser {
+    pub fn new(settings: Settings) -> io::Result<Visualiser> {
+        if let Some(dir) = &settings.frames {
+            fs::create_dir_all(dir)?;
+        }
+
+        let csv = match &settings.csv {
+            Some(path) => {
+                let mut csv = BufWriter::new(File::create(path)?);
+                writeln!(csv, "generation,occupied,empty")?;
+                Some(csv)
+            }
+            None => None,
+        };
+
+        if settings.animate {
+            // NOTE: Clear the screen once, every frame then starts from the top left.
+            print!("\x1b[2J");
+        }
+
+        Ok(Visualiser { settings, gif: None, csv })
+    }
+
+    /// Whether `show` does anything at all, so callers can skip building the seats.
+    pub fn is_active(&self) -> bool {
+        let settings = &self.settings;
+        settings.print || settings.animate || settings.frames.is_some() || settings.gif.is_some() || settings.csv.is_some()
+    }
+
+    pub fn show(&mut self, generation: usize, seats: &Seats) -> io::Result<()> {
+        if self.settings.print {
+            let mut stdout = io::stdout();
+            stdout.write_all(plain(seats).as_bytes())?;
+            writeln!(stdout)?;
+        }
+
+        if self.settings.animate {
+            let mut stdout = io::stdout();
+            stdout.write_all(ansi_frame(generation, seats).as_bytes())?;
+            stdout.flush()?;
+            thread::sleep(self.settings.delay);
+        }
+
+        if let Some(csv) = &mut self.csv {
+            let count = |seat| seats.iter().flatten().filter(|c| **c == seat).count();
+            writeln!(csv, "{},{},{}", generation, count('#'), count('L'))?;
+        }
+
+        if self.settings.frames.is_none() && self.settings.gif.is_none() {
+            return Ok(());
+        }
+
+        let picture = Picture::new(seats, self.settings.scale);
+
+        if let Some(dir) = &self.settings.frames {
+            let format = self.settings.format;
+            let path = dir.join(format!("generation_{:05}.{}", generation, format.extension()));
+            let output = BufWriter::new(File::create(path)?);
+            match format {
+                FrameFormat::Ppm => image::write_ppm(output, &picture)?,
+                FrameFormat::Png => image::write_png(output, &picture)?,
+            }
+        }
+
+        if let Some(path) = &self.settings.gif {
+            if self.gif.is_none() {
+                let output = BufWriter::new(File::create(path)?);
+                self.gif = Some(Gif::new(output, picture.width, picture.height)?);
+            }
+
+            // NOTE: GIF delays are in hundredths of a second.
+            let delay = (self.settings.delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+            self.gif.as_mut().unwrap().frame(&picture, delay)?;
+        }
+
+        Ok(())
+    }
+
+    /// Completes the files being written.
+    pub fn finish(self) -> io::Result<()> {
+        if let Some(gif) = self.gif {
+            gif.finish()?.flush()?;
+        }
+
+        if let Some(mut csv) = self.csv {
+            csv.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn plain(seats: &Seats) -> String {
+    let mut s = String::new();
+    for row in seats {
+        s.extend(row);
+        s.push('\n');
+    }
+
+    s
+}
+
+/// A frame of the terminal animation: the cursor moves to the top left, and
+/// empty seats are drawn in green and occupied ones in red.
+pub fn ansi_frame(generation: usize, seats: &Seats) -> String {
+    let mut frame = String::from("\x1b[H");
+    for row in seats {
+        for c in row {
+            match c {
+                'L' => frame.push_str("\x1b[32mL"),
+                '#' => frame.push_str("\x1b[31m#"),
+                c => {
+                    frame.push_str("\x1b[90m");
+                    frame.push(*c);
+                }
+            }
+        }
+        frame.push_str("\x1b[0m\n");
+    }
+
+    let occupied = seats.iter().flatten().filter(|c| **c == '#').count();
+    frame.push_str(&format!("generation {}, {} occupied\x1b[K\n", generation, occupied));
+
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::parse_seating;
+
+    #[test]
+    fn test_ansi_frame() {
+        let seats = parse_seating("L.\n#L");
+        assert_eq!(
+            ansi_frame(3, &seats),
+            "\x1b[H\x1b[32mL\x1b[90m.\x1b[0m\n\x1b[31m#\x1b[32mL\x1b[0m\ngeneration 3, 1 occupied\x1b[K\n"
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = std::env::temp_dir().join(format!("day11_visualise_{}", std::process::id()));
+        let settings = Settings {
+            frames: Some(dir.join("frames")),
+            format: FrameFormat::Ppm,
+            gif: Some(dir.join("seats.gif")),
+            csv: Some(dir.join("occupancy.csv")),
+            scale: 1,
+            ..Settings::default()
+        };
+
+        fs::create_dir_all(&dir).unwrap();
+        let mut visualiser = Visualiser::new(settings).unwrap();
+        assert!(visualiser.is_active());
+        visualiser.show(0, &parse_seating("LL\n.L")).unwrap();
+        visualiser.show(1, &parse_seating("##\n.#")).unwrap();
+        visualiser.finish().unwrap();
+
+        let csv = fs::read_to_string(dir.join("occupancy.csv")).unwrap();
+        assert_eq!(csv, "generation,occupied,empty\n0,0,3\n1,3,0\n");
+
+        let frame = fs::read(dir.join("frames").join("generation_00001.ppm")).unwrap();
+        assert_eq!(frame, b"P6\n2 2\n255\n\xd0\x30\x30\xd0\x30\x30\x20\x20\x20\xd0\x30\x30".to_vec());
+        assert!(dir.join("frames").join("generation_00000.ppm").exists());
+
+        let gif = fs::read(dir.join("seats.gif")).unwrap();
+        assert!(gif.starts_with(b"GIF89a\x02\x00\x02\x00"));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(!Visualiser::new(Settings::default()).unwrap().is_active());
+    }
+}
@@ -1,73 +1,119 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{self, BufReader};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+mod statistics;
+
+use statistics::Statistics;
+
+/// The answers of every person in a group, one `String` per person.
+type Group = Vec<String>;
+
+enum Mode {
+    Answers,
+    Statistics(Format),
+}
+
+enum Format {
+    Table,
+    Csv,
+}
 
 fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let mode = parse_mode(&args);
+
     let f = File::open("src/day6/input_day6.txt")?;
     let f = BufReader::new(f);
 
-    let mut groups: Vec<String> = vec![];
-    let mut group_size: Vec<usize> = vec![];
-    group_size.push(0);
-
-    let mut group = String::new();
+    let mut lines = vec![];
     for line in f.lines() {
-        let line = line.unwrap();
-        if line == "" {
-            groups.push(group);
-            group = String::new();
-            group_size.push(0);
-        } else {
-            let length = group_size.len();
-            let count = group_size[length - 1];
-            group_size[length - 1] = count + 1;
+        lines.push(line?);
+    }
+
+    let groups = parse_groups(&lines);
 
-            group.push_str(&line);
+    match mode {
+        Mode::Answers => {
+            let part1_answer = count_answers_part1(&groups);
+            let part2_answer = count_answers_part2(&groups);
+
+            println!("Part1: Number of yes answers: {}", part1_answer);
+            println!("Part2: Number of yes answers: {}", part2_answer);
+        }
+        Mode::Statistics(format) => {
+            let statistics = Statistics::from_groups(&groups);
+            match format {
+                Format::Table => statistics.print_table(),
+                Format::Csv => statistics.print_csv(),
+            }
         }
     }
 
-    groups.push(group);
+    Ok(())
+}
 
-    let part1_answer = count_answers_part1(&groups);
-    let part2_answer = count_answers_part2(&groups, &group_size);
+fn parse_mode(args: &[String]) -> Mode {
+    let mut mode = Mode::Answers;
+    if args.len() > 1 && args[1] == "stats" {
+        let format = match args.get(2).map(|s| s.as_str()) {
+            Some("csv") => Format::Csv,
+            _ => Format::Table,
+        };
 
-    println!("Part1: Number of yes answers: {}", part1_answer);
-    println!("Part2: Number of yes answers: {}", part2_answer);
+        mode = Mode::Statistics(format);
+    }
 
-    Ok(())
+    mode
 }
 
-fn count_answers_part1(groups: &Vec<String>) -> usize {
+fn parse_groups(lines: &[String]) -> Vec<Group> {
+    let mut groups: Vec<Group> = vec![];
+
+    let mut group: Group = vec![];
+    for line in lines {
+        if line.is_empty() {
+            if !group.is_empty() {
+                groups.push(group);
+            }
+            group = vec![];
+        } else {
+            group.push(line.clone());
+        }
+    }
+
+    if !group.is_empty() {
+        groups.push(group);
+    }
+
+    groups
+}
+
+fn count_answers_part1(groups: &[Group]) -> usize {
     let mut yes_answer_count = 0;
     for group in groups {
-        let mut chars: Vec<char> = group.chars().collect();
-        chars.sort_by(|a, b| a.cmp(b));
-        chars.dedup();
-        yes_answer_count += chars.len();
+        let answers: HashSet<char> = group.iter().flat_map(|person| person.chars()).collect();
+        yes_answer_count += answers.len();
     }
 
     yes_answer_count
 }
 
-fn count_answers_part2(groups: &Vec<String>, group_size: &Vec<usize>) -> usize {
+fn count_answers_part2(groups: &[Group]) -> usize {
     let mut yes_answer_count = 0;
-    for i in 0..groups.len() {
-        let group = &groups[i];
-        let group_count = group_size[i];
-
-        let chars: Vec<char> = group.chars().collect();
+    for group in groups {
         let mut answer_count: HashMap<char, usize> = HashMap::new();
-        for c in chars {
-            answer_count.entry(c).or_insert(0);
-            if answer_count.contains_key(&c) {
-                let count = answer_count.get_mut(&c).unwrap();
-                *count = *count + 1;
+        for person in group {
+            let answers: HashSet<char> = person.chars().collect();
+            for c in answers {
+                *answer_count.entry(c).or_insert(0) += 1;
             }
         }
 
-        for (_, count) in answer_count {
-            if count == group_count {
+        for count in answer_count.values() {
+            if *count == group.len() {
                 yes_answer_count += 1;
             }
         }
@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Default)]
+struct QuestionStats {
+    groups: usize,
+    people: usize,
+    unanimous_groups: usize,
+}
+
+#[derive(Debug)]
+struct GroupStats {
+    size: usize,
+    anyone: BTreeSet<char>,
+    everyone: BTreeSet<char>,
+}
+
+#[derive(Debug)]
+pub struct Statistics {
+    questions: BTreeMap<char, QuestionStats>,
+    group_sizes: BTreeMap<usize, usize>,
+    groups: Vec<GroupStats>,
+    people: usize,
+}
+
+impl Statistics {
+    pub fn from_groups(groups: &[Vec<String>]) -> Statistics {
+        let mut questions: BTreeMap<char, QuestionStats> = BTreeMap::new();
+        let mut group_sizes = BTreeMap::new();
+        let mut group_stats = vec![];
+        let mut people = 0;
+
+        for group in groups {
+            let mut answered_by: BTreeMap<char, usize> = BTreeMap::new();
+            for person in group {
+                let answers: BTreeSet<char> = person.chars().collect();
+                for c in answers {
+                    *answered_by.entry(c).or_insert(0) += 1;
+                }
+            }
+
+            let mut everyone = BTreeSet::new();
+            for (c, count) in &answered_by {
+                let question = questions.entry(*c).or_default();
+                question.groups += 1;
+                question.people += count;
+
+                if *count == group.len() {
+                    question.unanimous_groups += 1;
+                    everyone.insert(*c);
+                }
+            }
+
+            *group_sizes.entry(group.len()).or_insert(0) += 1;
+            people += group.len();
+
+            group_stats.push(GroupStats {
+                size: group.len(),
+                anyone: answered_by.keys().cloned().collect(),
+                everyone,
+            });
+        }
+
+        Statistics {
+            questions,
+            group_sizes,
+            groups: group_stats,
+            people,
+        }
+    }
+
+    /// Questions answered by the most people, ties included.
+    fn most_common(&self) -> Vec<char> {
+        let max = self.questions.values().map(|q| q.people).max();
+        self.questions_answered_by(max)
+    }
+
+    /// Questions answered by the fewest people (but at least one), ties included.
+    fn least_common(&self) -> Vec<char> {
+        let min = self.questions.values().map(|q| q.people).min();
+        self.questions_answered_by(min)
+    }
+
+    fn questions_answered_by(&self, people: Option<usize>) -> Vec<char> {
+        match people {
+            Some(people) => self.questions
+                .iter()
+                .filter(|(_, q)| q.people == people)
+                .map(|(c, _)| *c)
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn print_table(&self) {
+        println!("Groups: {}, People: {}", self.groups.len(), self.people);
+        println!("Most common: {}", format_answers(&self.most_common()));
+        println!("Least common: {}", format_answers(&self.least_common()));
+        println!();
+
+        println!("{:>8} {:>8} {:>8} {:>10}", "question", "groups", "people", "unanimous");
+        for (c, q) in &self.questions {
+            println!("{:>8} {:>8} {:>8} {:>10}", c, q.groups, q.people, q.unanimous_groups);
+        }
+        println!();
+
+        println!("{:>8} {:>8}", "size", "groups");
+        for (size, count) in &self.group_sizes {
+            println!("{:>8} {:>8}", size, count);
+        }
+        println!();
+
+        println!("{:>8} {:>8} {:>8} {:>8}  answers", "group", "size", "anyone", "everyone");
+        for (i, group) in self.groups.iter().enumerate() {
+            println!(
+                "{:>8} {:>8} {:>8} {:>8}  {}",
+                i + 1,
+                group.size,
+                group.anyone.len(),
+                group.everyone.len(),
+                group.anyone.iter().collect::<String>()
+            );
+        }
+    }
+
+    pub fn print_csv(&self) {
+        println!("question,groups,people,unanimous_groups");
+        for (c, q) in &self.questions {
+            println!("{},{},{},{}", c, q.groups, q.people, q.unanimous_groups);
+        }
+        println!();
+
+        println!("group_size,groups");
+        for (size, count) in &self.group_sizes {
+            println!("{},{}", size, count);
+        }
+        println!();
+
+        println!("group,size,anyone,everyone,answers,unanimous_answers");
+        for (i, group) in self.groups.iter().enumerate() {
+            println!(
+                "{},{},{},{},{},{}",
+                i + 1,
+                group.size,
+                group.anyone.len(),
+                group.everyone.len(),
+                group.anyone.iter().collect::<String>(),
+                group.everyone.iter().collect::<String>()
+            );
+        }
+    }
+}
+
+fn format_answers(answers: &[char]) -> String {
+    let answers: Vec<String> = answers.iter().map(|c| c.to_string()).collect();
+    answers.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<Vec<String>> {
+        let groups = vec![
+            vec!["abc"],
+            vec!["a", "b", "c"],
+            vec!["ab", "ac"],
+            vec!["a", "a", "a", "a"],
+            vec!["b"],
+        ];
+
+        groups
+            .into_iter()
+            .map(|g| g.into_iter().map(String::from).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_question_counts() {
+        let statistics = Statistics::from_groups(&example());
+
+        let a = &statistics.questions[&'a'];
+        assert_eq!((a.groups, a.people, a.unanimous_groups), (4, 8, 3));
+
+        let c = &statistics.questions[&'c'];
+        assert_eq!((c.groups, c.people, c.unanimous_groups), (3, 3, 1));
+
+        assert_eq!(statistics.most_common(), vec!['a']);
+        assert_eq!(statistics.least_common(), vec!['c']);
+    }
+
+    #[test]
+    fn test_group_sizes() {
+        let statistics = Statistics::from_groups(&example());
+
+        let sizes: Vec<(usize, usize)> = statistics.group_sizes.into_iter().collect();
+        assert_eq!(sizes, vec![(1, 2), (2, 1), (3, 1), (4, 1)]);
+        assert_eq!(statistics.people, 11);
+    }
+}
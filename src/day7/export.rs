@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+
+use crate::graph::BagGraph;
+
+/// Writes the graph as Graphviz DOT with edges labelled by count.
+///
+/// When `highlight` is given that bag is filled gold, the bags that can
+/// contain it light blue and the bags inside it pale green.
+pub fn to_dot(graph: &BagGraph, highlight: Option<usize>) -> String {
+    let mut s = String::new();
+    s.push_str("digraph bags {\n");
+    s.push_str("    node [shape=box];\n");
+
+    let (ancestors, descendants) = match highlight {
+        Some(id) => ((*graph.ancestors(id)).clone(), graph.descendants(id)),
+        None => (HashSet::new(), HashSet::new()),
+    };
+
+    for id in 0..graph.len() {
+        let fill = if highlight == Some(id) {
+            Some("gold")
+        } else if ancestors.contains(&id) {
+            Some("lightblue")
+        } else if descendants.contains(&id) {
+            Some("palegreen")
+        } else {
+            None
+        };
+
+        match fill {
+            Some(fill) => s.push_str(&format!(
+                "    {} [style=filled, fillcolor={}];\n",
+                quote(graph.colour(id)),
+                fill
+            )),
+            None => s.push_str(&format!("    {};\n", quote(graph.colour(id)))),
+        }
+    }
+
+    for id in 0..graph.len() {
+        for (child, count) in graph.contents(id) {
+            s.push_str(&format!(
+                "    {} -> {} [label=\"{}\"];\n",
+                quote(graph.colour(id)),
+                quote(graph.colour(*child)),
+                count
+            ));
+        }
+    }
+
+    s.push_str("}\n");
+
+    s
+}
+
+/// Writes the graph as a JSON object mapping each colour to its contents,
+/// e.g. `{"light red": [{"colour": "bright white", "count": 1}]}`.
+pub fn to_json(graph: &BagGraph) -> String {
+    let mut s = String::new();
+    s.push_str("{\n");
+
+    for id in 0..graph.len() {
+        let contents: Vec<String> = graph
+            .contents(id)
+            .iter()
+            .map(|(child, count)| {
+                format!("{{\"colour\": {}, \"count\": {}}}", quote(graph.colour(*child)), count)
+            })
+            .collect();
+
+        let separator = if id + 1 < graph.len() { "," } else { "" };
+        s.push_str(&format!(
+            "  {}: [{}]{}\n",
+            quote(graph.colour(id)),
+            contents.join(", "),
+            separator
+        ));
+    }
+
+    s.push_str("}\n");
+
+    s
+}
+
+/// Quotes a string for use as a DOT identifier or JSON string.
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::graph::example;
+
+    #[test]
+    fn test_dot() {
+        let graph = example();
+        let dot = to_dot(&graph, graph.id("dark olive"));
+
+        assert!(dot.starts_with("digraph bags {\n    node [shape=box];\n    \"light red\" [style=filled, fillcolor=lightblue];\n"));
+        assert!(dot.ends_with("    \"vibrant plum\" -> \"dotted black\" [label=\"6\"];\n}\n"));
+        assert_eq!(dot.lines().count(), 2 + 9 + 13 + 1);
+
+        assert!(dot.contains("\n    \"shiny gold\" [style=filled, fillcolor=lightblue];\n"));
+        assert!(dot.contains("\n    \"dark olive\" [style=filled, fillcolor=gold];\n"));
+        assert!(dot.contains("\n    \"vibrant plum\";\n"));
+        assert!(dot.contains("\n    \"dotted black\" [style=filled, fillcolor=palegreen];\n"));
+        assert!(dot.contains("\n    \"muted yellow\" -> \"faded blue\" [label=\"9\"];\n"));
+    }
+
+    #[test]
+    fn test_json() {
+        let expected = "{\n\
+            \x20 \"light red\": [{\"colour\": \"bright white\", \"count\": 1}, {\"colour\": \"muted yellow\", \"count\": 2}],\n\
+            \x20 \"bright white\": [{\"colour\": \"shiny gold\", \"count\": 1}],\n\
+            \x20 \"muted yellow\": [{\"colour\": \"shiny gold\", \"count\": 2}, {\"colour\": \"faded blue\", \"count\": 9}],\n\
+            \x20 \"dark orange\": [{\"colour\": \"bright white\", \"count\": 3}, {\"colour\": \"muted yellow\", \"count\": 4}],\n\
+            \x20 \"shiny gold\": [{\"colour\": \"dark olive\", \"count\": 1}, {\"colour\": \"vibrant plum\", \"count\": 2}],\n\
+            \x20 \"faded blue\": [],\n\
+            \x20 \"dark olive\": [{\"colour\": \"faded blue\", \"count\": 3}, {\"colour\": \"dotted black\", \"count\": 4}],\n\
+            \x20 \"vibrant plum\": [{\"colour\": \"faded blue\", \"count\": 5}, {\"colour\": \"dotted black\", \"count\": 6}],\n\
+            \x20 \"dotted black\": []\n\
+            }\n";
+
+        assert_eq!(to_json(&example()), expected);
+    }
+}
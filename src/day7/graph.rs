@@ -0,0 +1,328 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+pub type Rule = (String, Vec<(String, usize)>);
+
+/// The number of bags inside bag `id` doesn't fit in a `usize`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overflow {
+    pub id: usize,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "more than {}", usize::MAX)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    Unvisited,
+    InProgress,
+    Done,
+}
+
+/// Bag rules as a graph of interned colours, with edges in both directions.
+///
+/// `contains[a]` holds `(b, n)` when bag `a` directly holds `n` bags of
+/// colour `b`, and `contained_by[b]` holds the matching `(a, n)`.
+#[derive(Debug)]
+pub struct BagGraph {
+    colours: Vec<String>,
+    ids: HashMap<String, usize>,
+    contains: Vec<Vec<(usize, usize)>>,
+    contained_by: Vec<Vec<(usize, usize)>>,
+    ancestors: RefCell<HashMap<usize, Rc<HashSet<usize>>>>,
+    contained_counts: RefCell<Vec<Option<Result<usize, Overflow>>>>,
+}
+
+impl BagGraph {
+    pub fn from_rules(rules: &[Rule]) -> BagGraph {
+        let mut graph = BagGraph {
+            colours: vec![],
+            ids: HashMap::new(),
+            contains: vec![],
+            contained_by: vec![],
+            ancestors: RefCell::new(HashMap::new()),
+            contained_counts: RefCell::new(vec![]),
+        };
+
+        for (colour, children) in rules {
+            let parent = graph.intern(colour);
+            for (child, count) in children {
+                let child = graph.intern(child);
+                graph.contains[parent].push((child, *count));
+                graph.contained_by[child].push((parent, *count));
+            }
+        }
+
+        graph.contained_counts = RefCell::new(vec![None; graph.colours.len()]);
+
+        graph
+    }
+
+    fn intern(&mut self, colour: &str) -> usize {
+        if let Some(id) = self.ids.get(colour) {
+            return *id;
+        }
+
+        let id = self.colours.len();
+        self.colours.push(colour.to_string());
+        self.ids.insert(colour.to_string(), id);
+        self.contains.push(vec![]);
+        self.contained_by.push(vec![]);
+
+        id
+    }
+
+    pub fn id(&self, colour: &str) -> Option<usize> {
+        self.ids.get(colour).cloned()
+    }
+
+    pub fn colour(&self, id: usize) -> &str {
+        &self.colours[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.colours.len()
+    }
+
+    /// The bags directly inside `id`, with their counts.
+    pub fn contents(&self, id: usize) -> &[(usize, usize)] {
+        &self.contains[id]
+    }
+
+    /// The bags that directly hold `id`, with how many of it they hold.
+    pub fn containers(&self, id: usize) -> &[(usize, usize)] {
+        &self.contained_by[id]
+    }
+
+    /// All bags eventually inside `id`.
+    pub fn descendants(&self, id: usize) -> HashSet<usize> {
+        let mut descendants = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            for (child, _) in &self.contains[current] {
+                if descendants.insert(*child) {
+                    stack.push(*child);
+                }
+            }
+        }
+
+        descendants
+    }
+
+    /// Finds cycles with a depth-first search, one per back edge.
+    ///
+    /// Each cycle is the path from the repeated bag back down to itself, so
+    /// the first and last entries are the same bag.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut cycles = vec![];
+        let mut state = vec![Visit::Unvisited; self.colours.len()];
+
+        for start in 0..self.colours.len() {
+            if state[start] != Visit::Unvisited {
+                continue;
+            }
+
+            // NOTE: Each stack entry is a bag and the index of the next child to visit.
+            let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+            state[start] = Visit::InProgress;
+
+            while let Some((current, next_child)) = stack.last_mut() {
+                let current = *current;
+                if let Some((child, _)) = self.contains[current].get(*next_child) {
+                    *next_child += 1;
+                    let child = *child;
+
+                    match state[child] {
+                        Visit::Unvisited => {
+                            state[child] = Visit::InProgress;
+                            stack.push((child, 0));
+                        }
+                        Visit::InProgress => {
+                            let begin = stack.iter().position(|(id, _)| *id == child).unwrap();
+                            let mut cycle: Vec<usize> = stack[begin..].iter().map(|(id, _)| *id).collect();
+                            cycle.push(child);
+                            cycles.push(cycle);
+                        }
+                        Visit::Done => (),
+                    }
+                } else {
+                    state[current] = Visit::Done;
+                    stack.pop();
+                }
+            }
+        }
+
+        cycles
+    }
+
+    /// All bags that eventually contain `id`, found by walking the reverse index.
+    pub fn ancestors(&self, id: usize) -> Rc<HashSet<usize>> {
+        if let Some(ancestors) = self.ancestors.borrow().get(&id) {
+            return ancestors.clone();
+        }
+
+        let mut ancestors = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            for (parent, _) in &self.contained_by[current] {
+                if ancestors.insert(*parent) {
+                    stack.push(*parent);
+                }
+            }
+        }
+
+        let ancestors = Rc::new(ancestors);
+        self.ancestors.borrow_mut().insert(id, ancestors.clone());
+
+        ancestors
+    }
+
+    /// Total number of bags inside `id`.
+    ///
+    /// Subtotals are memoised per colour and computed with an explicit stack,
+    /// so deep rule chains don't overflow the call stack. Totals multiply
+    /// along every level, so a deep enough chain doesn't fit in a `usize`
+    /// either, which is reported for the bag where it first happens.
+    pub fn contained_count(&self, id: usize) -> Result<usize, Overflow> {
+        if let Some(count) = self.contained_counts.borrow()[id] {
+            return count;
+        }
+
+        let mut counts = self.contained_counts.borrow_mut();
+        let mut state = vec![Visit::Unvisited; self.colours.len()];
+        let mut stack = vec![id];
+
+        while let Some(&current) = stack.last() {
+            if counts[current].is_some() {
+                stack.pop();
+                continue;
+            }
+
+            match state[current] {
+                Visit::Unvisited => {
+                    state[current] = Visit::InProgress;
+                    for (child, _) in &self.contains[current] {
+                        if counts[*child].is_none() && state[*child] == Visit::Unvisited {
+                            stack.push(*child);
+                        }
+                    }
+                }
+                _ => {
+                    // NOTE: A child still in progress means the rules contain a
+                    // cycle, its contribution is left out rather than looping forever.
+                    let mut total = Ok(0);
+                    for (child, count) in &self.contains[current] {
+                        total = total.and_then(|total: usize| {
+                            let subtotal = counts[*child].unwrap_or(Ok(0))?;
+                            subtotal
+                                .checked_add(1)
+                                .and_then(|bags| bags.checked_mul(*count))
+                                .and_then(|bags| bags.checked_add(total))
+                                .ok_or(Overflow { id: current })
+                        });
+                    }
+
+                    counts[current] = Some(total);
+                    state[current] = Visit::Done;
+                    stack.pop();
+                }
+            }
+        }
+
+        counts[id].unwrap()
+    }
+}
+
+/// Builds a rule from string slices.
+#[cfg(test)]
+pub fn rule(colour: &str, children: &[(&str, usize)]) -> Rule {
+    let children = children.iter().map(|(c, n)| (c.to_string(), *n)).collect();
+    (colour.to_string(), children)
+}
+
+/// The graph of the puzzle's example rules.
+#[cfg(test)]
+pub fn example() -> BagGraph {
+    BagGraph::from_rules(&[
+        rule("light red", &[("bright white", 1), ("muted yellow", 2)]),
+        rule("dark orange", &[("bright white", 3), ("muted yellow", 4)]),
+        rule("bright white", &[("shiny gold", 1)]),
+        rule("muted yellow", &[("shiny gold", 2), ("faded blue", 9)]),
+        rule("shiny gold", &[("dark olive", 1), ("vibrant plum", 2)]),
+        rule("dark olive", &[("faded blue", 3), ("dotted black", 4)]),
+        rule("vibrant plum", &[("faded blue", 5), ("dotted black", 6)]),
+        rule("faded blue", &[]),
+        rule("dotted black", &[]),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example() {
+        let graph = example();
+        let shiny_gold = graph.id("shiny gold").unwrap();
+
+        assert_eq!(graph.ancestors(shiny_gold).len(), 4);
+        assert_eq!(graph.contained_count(shiny_gold), Ok(32));
+    }
+
+    #[test]
+    fn test_cycles() {
+        let graph = BagGraph::from_rules(&[
+            rule("light red", &[("bright white", 1)]),
+            rule("bright white", &[("muted yellow", 2)]),
+            rule("muted yellow", &[("light red", 1), ("faded blue", 1)]),
+            rule("faded blue", &[]),
+        ]);
+
+        let cycles = graph.cycles();
+        assert_eq!(cycles.len(), 1);
+
+        let path: Vec<&str> = cycles[0].iter().map(|id| graph.colour(*id)).collect();
+        assert_eq!(path, vec!["light red", "bright white", "muted yellow", "light red"]);
+
+        assert!(example().cycles().is_empty());
+    }
+
+    #[test]
+    fn test_deep_chain() {
+        let n = 100_000;
+        let mut rules = vec![];
+        for i in 0..n {
+            rules.push(rule(&format!("bag {}", i), &[(&format!("bag {}", i + 1), 1)]));
+        }
+        rules.push(rule(&format!("bag {}", n), &[]));
+
+        let graph = BagGraph::from_rules(&rules);
+
+        assert_eq!(graph.contained_count(graph.id("bag 0").unwrap()), Ok(n));
+        assert_eq!(graph.ancestors(graph.id(&format!("bag {}", n)).unwrap()).len(), n);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // NOTE: Each bag holds 2 of the next, so bag `i` holds 2^(81 - i) - 2 bags.
+        let n = 80;
+        let mut rules = vec![];
+        for i in 0..n {
+            rules.push(rule(&format!("bag {}", i), &[(&format!("bag {}", i + 1), 2)]));
+        }
+        rules.push(rule(&format!("bag {}", n), &[]));
+
+        let graph = BagGraph::from_rules(&rules);
+        let id = |i: usize| graph.id(&format!("bag {}", i)).unwrap();
+
+        // NOTE: Bag 17 holds 2^64 - 2 bags, which still fits, bag 16 doesn't.
+        assert_eq!(graph.contained_count(id(0)), Err(Overflow { id: id(16) }));
+        assert_eq!(graph.contained_count(id(16)), Err(Overflow { id: id(16) }));
+        assert_eq!(graph.contained_count(id(17)), Ok(usize::MAX - 1));
+    }
+}
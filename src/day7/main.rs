@@ -1,97 +1,173 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{self, BufReader};
-use std::collections::HashMap;
+use std::env;
 
-fn main() -> io::Result<()> {
-    let f = File::open("src/day7/input_day7.txt")?;
-    let f = BufReader::new(f);
+mod export;
+mod graph;
+mod paths;
+mod validate;
 
-    let mut nodes: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+use graph::BagGraph;
 
-    for line in f.lines() {
-        let line = line.unwrap();
+enum Command {
+    Count,
+    Dot,
+    Json,
+    Paths,
+    Explain,
+}
 
-        let parts: Vec<&str> = line.split(" contain ").collect();
-        let first = parts.first().unwrap();
-        let second = parts.last().unwrap();
-        let children: Vec<&str> = second.split(", ").collect();
+struct Options {
+    command: Command,
+    input: String,
+    colour: Option<String>,
+    max_depth: Option<usize>,
+    top: usize,
+}
 
-        let mut cs: Vec<(String, usize)> = vec![];
-        for child in children {
-            if child == "no other bags." {
-                continue;
-            }
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let options = parse_options(&args);
 
-            let parts: Vec<&str> = child.split(" ").collect();
-            if parts.len() == 4 {
-                let count: usize = parts[0].parse().unwrap();
-                let first = parts[1];
-                let second = parts[2];
+    let f = File::open(&options.input)?;
+    let f = BufReader::new(f);
 
-                let mut c = String::new();
-                c.push_str(first);
-                c.push_str(" ");
-                c.push_str(second);
+    let mut lines = vec![];
+    for line in f.lines() {
+        lines.push(line?);
+    }
 
-                cs.push((c, count));
-            }
-        }
+    let (rules, mut problems) = validate::parse_rules(&lines);
+    let graph = BagGraph::from_rules(&rules);
+    problems.append(&mut validate::check_cycles(&graph));
 
-        let parts: Vec<&str> = first.split(" ").collect();
-        let first = parts[0];
-        let second = parts[1];
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        eprintln!("{} problem(s) found in {}", problems.len(), options.input);
+        std::process::exit(1);
+    }
 
-        let mut key = String::new();
-        key.push_str(first);
-        key.push_str(" ");
-        key.push_str(second);
+    let colour = options.colour.as_ref().map(|colour| (colour, find_colour(&graph, colour)));
 
-        nodes.insert(key.to_string(), cs);
-    }
+    match options.command {
+        Command::Count => {
+            let shiny_gold = String::from("shiny gold");
+            let (colour, id) = colour.unwrap_or_else(|| (&shiny_gold, find_colour(&graph, &shiny_gold)));
 
-    let s = "shiny gold".to_string();
-    let mut count = 0;
-    for key in nodes.keys() {
-        if find(&s, key, &nodes) {
-            count += 1;
+            println!("Bags that can contain {}: {}", colour, graph.ancestors(id).len());
+            match graph.contained_count(id) {
+                Ok(count) => println!("Bags inside {}: {}", colour, count),
+                Err(overflow) => println!("Bags inside {}: {}", colour, overflow),
+            }
+        }
+        Command::Dot => print!("{}", export::to_dot(&graph, colour.map(|(_, id)| id))),
+        Command::Json => print!("{}", export::to_json(&graph)),
+        Command::Paths => {
+            let (colour, id) = require_colour(colour);
+
+            let paths = paths::containment_paths(&graph, id, options.max_depth);
+            for path in &paths {
+                let bags: Vec<&str> = path.bags.iter().map(|id| graph.colour(*id)).collect();
+                let prefix = if path.truncated { "... > " } else { "" };
+                println!("{}{}", prefix, bags.join(" > "));
+            }
+            println!("{} path(s) lead to {}", paths.len(), colour);
+        }
+        Command::Explain => {
+            let (colour, id) = require_colour(colour);
+
+            print!("{}", paths::explain(&graph, id, options.max_depth));
+
+            println!();
+            println!("Top contributors to {}:", colour);
+            match paths::top_contributors(&graph, id, options.top) {
+                Ok(top) => {
+                    for (contributor, total) in top {
+                        println!("{:>12} {}", total, graph.colour(contributor));
+                    }
+                }
+                Err(overflow) => println!("{} contains {} bags", colour, overflow),
+            }
         }
     }
 
-    println!("Part 1: {}", count);
-    println!("Part 2: {}", count_bags(&s, &nodes));
-
     Ok(())
 }
 
-fn find(needle: &String, key: &String, nodes: &HashMap<String, Vec<(String, usize)>>) -> bool {
-    let children = nodes.get(key).unwrap();
-    if children.is_empty() {
-        return false
+fn require_colour(colour: Option<(&String, usize)>) -> (&String, usize) {
+    match colour {
+        Some(colour) => colour,
+        None => {
+            eprintln!("A bag colour is required");
+            std::process::exit(1);
+        }
     }
+}
 
-    for (child, _) in children {
-        if child == needle {
-            return true;
-        } else if find(needle, child, nodes) {
-            return true;
+fn find_colour(graph: &BagGraph, colour: &str) -> usize {
+    match graph.id(colour) {
+        Some(id) => id,
+        None => {
+            eprintln!("Unknown bag colour: {}", colour);
+            std::process::exit(1);
         }
     }
-
-    false
 }
 
-fn count_bags(needle: &String, nodes: &HashMap<String, Vec<(String, usize)>>) -> usize {
-    let mut total_count = 0;
-    let children = nodes.get(needle).unwrap();
-    if children.is_empty() {
-        return total_count;
+/// `day7 [count|dot|json|paths|explain] [--input <file>] [--max-depth <n>] [--top <k>] [colour]`
+///
+/// The colour may be given either quoted or as separate words. `count`
+/// defaults to "shiny gold", `dot` highlights the colour if one is given and
+/// `paths` and `explain` require one.
+fn parse_options(args: &[String]) -> Options {
+    let mut command = Command::Count;
+    let mut input = String::from("src/day7/input_day7.txt");
+    let mut max_depth = None;
+    let mut top = 5;
+    let mut words: Vec<&str> = vec![];
+
+    let mut args = args.iter().skip(1).peekable();
+    if let Some(arg) = args.peek() {
+        let parsed = match arg.as_str() {
+            "count" => Some(Command::Count),
+            "dot" => Some(Command::Dot),
+            "json" => Some(Command::Json),
+            "paths" => Some(Command::Paths),
+            "explain" => Some(Command::Explain),
+            _ => None,
+        };
+
+        if let Some(parsed) = parsed {
+            command = parsed;
+            args.next();
+        }
     }
 
-    for (key, count) in children {
-        total_count += count + (count * count_bags(key, nodes));
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                if let Some(path) = args.next() {
+                    input = path.clone();
+                }
+            }
+            "--max-depth" => max_depth = args.next().and_then(|n| n.parse().ok()),
+            "--top" => {
+                if let Some(k) = args.next().and_then(|k| k.parse().ok()) {
+                    top = k;
+                }
+            }
+            word => words.push(word),
+        }
     }
 
+    let colour = if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    };
 
-    total_count
-}
\ No newline at end of file
+    Options { command, input, colour, max_depth, top }
+}
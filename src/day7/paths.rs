@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use crate::graph::{BagGraph, Overflow};
+
+#[derive(Debug, PartialEq)]
+pub struct Path {
+    /// Bags from the outermost one down to the queried bag.
+    pub bags: Vec<usize>,
+    /// Set when the walk stopped at the depth limit before reaching an outermost bag.
+    pub truncated: bool,
+}
+
+/// Every chain of bags that ends with `id`, following the reverse index
+/// upwards until a bag that nothing contains.
+///
+/// With `max_depth` the walk stops after that many levels and the path is
+/// marked as truncated.
+pub fn containment_paths(graph: &BagGraph, id: usize, max_depth: Option<usize>) -> Vec<Path> {
+    let mut paths = vec![];
+
+    // NOTE: Paths are built from the queried bag outwards and reversed when complete.
+    let mut stack = vec![vec![id]];
+    while let Some(path) = stack.pop() {
+        let current = *path.last().unwrap();
+        let parents = graph.containers(current);
+
+        let depth = path.len() - 1;
+        let at_limit = max_depth.is_some_and(|max_depth| depth >= max_depth);
+
+        if parents.is_empty() || at_limit {
+            let mut bags = path;
+            bags.reverse();
+            paths.push(Path { bags, truncated: !parents.is_empty() });
+            continue;
+        }
+
+        for (parent, _) in parents.iter().rev() {
+            let mut next = path.clone();
+            next.push(*parent);
+            stack.push(next);
+        }
+    }
+
+    paths
+}
+
+/// Expands the contents of `id` as an indented tree.
+///
+/// Each line shows how many bags its parent holds, how many there are in
+/// total (the counts multiplied along the path) and what they add to the
+/// parent's subtotal, `count * (1 + inside)`. Numbers too large for a
+/// `usize` are shown as such.
+pub fn explain(graph: &BagGraph, id: usize, max_depth: Option<usize>) -> String {
+    let mut s = format!("{}: {} bags inside\n", graph.colour(id), show(graph.contained_count(id).ok(), id));
+
+    // NOTE: Stack entries are (bag, count in parent, total count, depth).
+    let mut stack: Vec<(usize, usize, Option<usize>, usize)> = vec![];
+    for (child, count) in graph.contents(id).iter().rev() {
+        stack.push((*child, *count, Some(*count), 1));
+    }
+
+    while let Some((current, count, total, depth)) = stack.pop() {
+        let inside = graph.contained_count(current).ok();
+        let subtotal = inside.and_then(|inside| inside.checked_add(1)).and_then(|bags| bags.checked_mul(count));
+        let contents = graph.contents(current);
+        let at_limit = max_depth.is_some_and(|max_depth| depth >= max_depth);
+
+        s.push_str(&format!(
+            "{}{} {} (x{} in total): {} * (1 + {}) = {}{}\n",
+            "  ".repeat(depth),
+            count,
+            graph.colour(current),
+            show(total, current),
+            count,
+            show(inside, current),
+            show(subtotal, current),
+            if at_limit && !contents.is_empty() { " ..." } else { "" }
+        ));
+
+        if at_limit {
+            continue;
+        }
+
+        for (child, child_count) in contents.iter().rev() {
+            let total = total.and_then(|total| total.checked_mul(*child_count));
+            stack.push((*child, *child_count, total, depth + 1));
+        }
+    }
+
+    s
+}
+
+fn show(count: Option<usize>, id: usize) -> String {
+    match count {
+        Some(count) => count.to_string(),
+        None => Overflow { id }.to_string(),
+    }
+}
+
+/// The `k` colours that make up most of the bags inside `id`, with how
+/// many of each there are in total, or the bag whose total doesn't fit in
+/// a `usize`.
+pub fn top_contributors(graph: &BagGraph, id: usize, k: usize) -> Result<Vec<(usize, usize)>, Overflow> {
+    let descendants = graph.descendants(id);
+
+    // NOTE: Kahn's algorithm over the bags inside `id`, so every bag has
+    // received the totals from all of its containers before passing them on.
+    let mut remaining: HashMap<usize, usize> = HashMap::new();
+    for bag in descendants.iter() {
+        let containers = graph
+            .containers(*bag)
+            .iter()
+            .filter(|(parent, _)| *parent == id || descendants.contains(parent))
+            .count();
+        remaining.insert(*bag, containers);
+    }
+
+    let mut totals: HashMap<usize, usize> = HashMap::new();
+    let mut queue: Vec<(usize, usize)> = vec![(id, 1)];
+    while let Some((current, total)) = queue.pop() {
+        for (child, count) in graph.contents(current) {
+            let bags = total.checked_mul(*count).ok_or(Overflow { id: *child })?;
+            let child_total = totals.entry(*child).or_insert(0);
+            *child_total = child_total.checked_add(bags).ok_or(Overflow { id: *child })?;
+
+            let left = remaining.get_mut(child).unwrap();
+            *left -= 1;
+            if *left == 0 {
+                queue.push((*child, totals[child]));
+            }
+        }
+    }
+
+    let mut totals: Vec<(usize, usize)> = totals.into_iter().collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| graph.colour(a.0).cmp(graph.colour(b.0))));
+    totals.truncate(k);
+
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::graph::{example, rule};
+
+    fn colours(graph: &BagGraph, path: &Path) -> Vec<String> {
+        path.bags.iter().map(|id| graph.colour(*id).to_string()).collect()
+    }
+
+    #[test]
+    fn test_containment_paths() {
+        let graph = example();
+        let shiny_gold = graph.id("shiny gold").unwrap();
+
+        let paths = containment_paths(&graph, shiny_gold, None);
+        let paths: Vec<String> = paths.iter().map(|p| colours(&graph, p).join(" > ")).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "light red > bright white > shiny gold",
+                "dark orange > bright white > shiny gold",
+                "light red > muted yellow > shiny gold",
+                "dark orange > muted yellow > shiny gold",
+            ]
+        );
+
+        let paths = containment_paths(&graph, shiny_gold, Some(1));
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.truncated && p.bags.len() == 2));
+    }
+
+    #[test]
+    fn test_explain() {
+        let graph = example();
+        let shiny_gold = graph.id("shiny gold").unwrap();
+
+        let expected = "shiny gold: 32 bags inside\n\
+            \x20 1 dark olive (x1 in total): 1 * (1 + 7) = 8 ...\n\
+            \x20 2 vibrant plum (x2 in total): 2 * (1 + 11) = 24 ...\n";
+        assert_eq!(explain(&graph, shiny_gold, Some(1)), expected);
+
+        let tree = explain(&graph, shiny_gold, None);
+        assert_eq!(tree.lines().count(), 7);
+        assert!(tree.contains("\n    6 dotted black (x12 in total): 6 * (1 + 0) = 6\n"));
+    }
+
+    #[test]
+    fn test_top_contributors() {
+        let graph = example();
+        let shiny_gold = graph.id("shiny gold").unwrap();
+
+        let top = top_contributors(&graph, shiny_gold, 10).unwrap();
+        let top: Vec<(&str, usize)> = top.iter().map(|(id, n)| (graph.colour(*id), *n)).collect();
+        assert_eq!(
+            top,
+            vec![("dotted black", 16), ("faded blue", 13), ("vibrant plum", 2), ("dark olive", 1)]
+        );
+        assert_eq!(top.iter().map(|(_, n)| n).sum::<usize>(), 32);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // NOTE: 70 levels of bags each holding 2 of the next.
+        let mut rules: Vec<_> = (0..70).map(|i| rule(&format!("bag {}", i), &[(&format!("bag {}", i + 1), 2)])).collect();
+        rules.push(rule("bag 70", &[]));
+        let graph = BagGraph::from_rules(&rules);
+        let id = |i: usize| graph.id(&format!("bag {}", i)).unwrap();
+
+        assert_eq!(top_contributors(&graph, id(0), 3), Err(Overflow { id: id(64) }));
+        assert_eq!(top_contributors(&graph, id(10), 1), Ok(vec![(id(70), 1 << 60)]));
+
+        let tree = explain(&graph, id(0), Some(1));
+        assert!(tree.starts_with(&format!("bag 0: more than {} bags inside\n", usize::MAX)));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::graph::{BagGraph, Rule};
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Malformed { line: usize, reason: String },
+    Duplicate { colour: String, line: usize, first_line: usize },
+    Undefined { colour: String, line: usize },
+    Cycle { path: Vec<String> },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Malformed { line, reason } => {
+                write!(f, "line {}: malformed rule: {}", line, reason)
+            }
+            Problem::Duplicate { colour, line, first_line } => write!(
+                f,
+                "line {}: duplicate rule for {} (first defined on line {})",
+                line, colour, first_line
+            ),
+            Problem::Undefined { colour, line } => {
+                write!(f, "line {}: {} has no rule of its own", line, colour)
+            }
+            Problem::Cycle { path } => write!(f, "cycle: {}", path.join(" -> ")),
+        }
+    }
+}
+
+/// Parses every line strictly, keeping the first definition of each colour.
+///
+/// Malformed lines, duplicate definitions and references to colours that
+/// never get a rule of their own are returned as problems. Line numbers start at 1.
+pub fn parse_rules(lines: &[String]) -> (Vec<Rule>, Vec<Problem>) {
+    let mut rules = vec![];
+    let mut problems = vec![];
+    let mut defined: HashMap<String, usize> = HashMap::new();
+    let mut referenced: Vec<(String, usize)> = vec![];
+
+    for (i, line) in lines.iter().enumerate() {
+        let line_number = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (colour, children) = match parse_rule(line) {
+            Ok(rule) => rule,
+            Err(reason) => {
+                problems.push(Problem::Malformed { line: line_number, reason });
+                continue;
+            }
+        };
+
+        if let Some(first_line) = defined.get(&colour) {
+            problems.push(Problem::Duplicate { colour, line: line_number, first_line: *first_line });
+            continue;
+        }
+
+        defined.insert(colour.clone(), line_number);
+        for (child, _) in &children {
+            referenced.push((child.clone(), line_number));
+        }
+
+        rules.push((colour, children));
+    }
+
+    for (colour, line) in referenced {
+        if !defined.contains_key(&colour) {
+            problems.push(Problem::Undefined { colour, line });
+        }
+    }
+
+    (rules, problems)
+}
+
+/// Parses `<colour> bags contain <n> <colour> bag(s), ... .` or
+/// `<colour> bags contain no other bags.`
+pub fn parse_rule(line: &str) -> Result<Rule, String> {
+    let line = line.trim();
+
+    let (outer, inner) = match line.find(" bags contain ") {
+        Some(i) => (&line[..i], &line[i + " bags contain ".len()..]),
+        None => return Err(format!("expected '<colour> bags contain ...', got '{}'", line)),
+    };
+
+    if outer.is_empty() {
+        return Err(String::from("missing outer bag colour"));
+    }
+
+    let inner = match inner.strip_suffix('.') {
+        Some(inner) => inner,
+        None => return Err(format!("missing trailing '.' in '{}'", line)),
+    };
+
+    if inner == "no other bags" {
+        return Ok((outer.to_string(), vec![]));
+    }
+
+    let mut children = vec![];
+    for child in inner.split(", ") {
+        children.push(parse_content(child)?);
+    }
+
+    Ok((outer.to_string(), children))
+}
+
+fn parse_content(s: &str) -> Result<(String, usize), String> {
+    let words: Vec<&str> = s.split(' ').collect();
+    if words.len() < 3 {
+        return Err(format!("expected '<count> <colour> bag(s)', got '{}'", s));
+    }
+
+    let count: usize = match words[0].parse() {
+        Ok(count) if count > 0 => count,
+        _ => return Err(format!("invalid count '{}' in '{}'", words[0], s)),
+    };
+
+    let noun = words[words.len() - 1];
+    let expected = if count == 1 { "bag" } else { "bags" };
+    if noun != expected {
+        return Err(format!("expected '{}' after a count of {} in '{}'", expected, count, s));
+    }
+
+    let colour = words[1..words.len() - 1].join(" ");
+
+    Ok((colour, count))
+}
+
+pub fn check_cycles(graph: &BagGraph) -> Vec<Problem> {
+    graph
+        .cycles()
+        .into_iter()
+        .map(|cycle| Problem::Cycle {
+            path: cycle.iter().map(|id| graph.colour(*id).to_string()).collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = parse_rule("light red bags contain 1 bright white bag, 2 muted yellow bags.");
+        let expected = (
+            String::from("light red"),
+            vec![(String::from("bright white"), 1), (String::from("muted yellow"), 2)],
+        );
+        assert_eq!(rule, Ok(expected));
+
+        let rule = parse_rule("faded blue bags contain no other bags.");
+        assert_eq!(rule, Ok((String::from("faded blue"), vec![])));
+
+        assert!(parse_rule("1 bright white bag").is_err());
+        assert!(parse_rule("light red bags contain 2 bright white bag.").is_err());
+        assert!(parse_rule("light red bags contain 1 bright white bags.").is_err());
+        assert!(parse_rule("light red bags contain no other bags").is_err());
+    }
+
+    #[test]
+    fn test_problems() {
+        let (rules, problems) = parse_rules(&lines(
+            "light red bags contain 1 bright white bag.\n\
+             bright white bags contain 2 muted yellow bags.\n\
+             1 bright white bag\n\
+             light red bags contain no other bags.",
+        ));
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            problems,
+            vec![
+                Problem::Malformed {
+                    line: 3,
+                    reason: String::from("expected '<colour> bags contain ...', got '1 bright white bag'"),
+                },
+                Problem::Duplicate { colour: String::from("light red"), line: 4, first_line: 1 },
+                Problem::Undefined { colour: String::from("muted yellow"), line: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycle_problem() {
+        let (rules, _) = parse_rules(&lines(
+            "light red bags contain 1 bright white bag.\n\
+             bright white bags contain 2 light red bags.",
+        ));
+        let graph = BagGraph::from_rules(&rules);
+
+        let problems = check_cycles(&graph);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].to_string(), "cycle: light red -> bright white -> light red");
+    }
+}
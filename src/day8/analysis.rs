@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+
+use crate::isa::Op;
+
+/// A run of instructions that is only ever entered at its first instruction
+/// and only left after its last.
+#[derive(Debug, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    /// Where execution continues after the block. Addresses may be the end
+    /// of the program or lie outside it altogether.
+    pub targets: Vec<isize>,
+}
+
+#[derive(Debug)]
+pub struct Analysis {
+    len: usize,
+    pub reachable: Vec<bool>,
+    pub blocks: Vec<Block>,
+    /// Strongly connected components that can run forever, each sorted by address.
+    pub loops: Vec<Vec<usize>>,
+    /// Jumps whose target lies before the first or past the end of the program.
+    pub escaping_jumps: Vec<(usize, isize)>,
+}
+
+/// Analyses a program without running it.
+pub fn analyze(ops: &[Op]) -> Analysis {
+    let len = ops.len();
+    let successors: Vec<Vec<usize>> = ops
+        .iter()
+        .enumerate()
+        .map(|(ip, op)| {
+            op.targets(ip)
+                .into_iter()
+                .filter(|target| *target >= 0 && (*target as usize) < len)
+                .map(|target| target as usize)
+                .collect()
+        })
+        .collect();
+
+    let mut reachable = vec![false; len];
+    if len > 0 {
+        reachable[0] = true;
+        let mut stack = vec![0];
+        while let Some(current) = stack.pop() {
+            for next in &successors[current] {
+                if !reachable[*next] {
+                    reachable[*next] = true;
+                    stack.push(*next);
+                }
+            }
+        }
+    }
+
+    let mut escaping_jumps = vec![];
+    for (ip, op) in ops.iter().enumerate() {
+        for target in op.targets(ip) {
+            if target < 0 || target > len as isize {
+                escaping_jumps.push((ip, target));
+            }
+        }
+    }
+
+    Analysis {
+        len,
+        reachable,
+        blocks: basic_blocks(ops),
+        loops: loops(&successors),
+        escaping_jumps,
+    }
+}
+
+fn basic_blocks(ops: &[Op]) -> Vec<Block> {
+    let len = ops.len();
+
+    let mut leaders = HashSet::new();
+    leaders.insert(0);
+    for (ip, op) in ops.iter().enumerate() {
+        let targets = op.targets(ip);
+        if targets != vec![ip as isize + 1] {
+            leaders.insert(ip + 1);
+            for target in targets {
+                if target >= 0 && (target as usize) < len {
+                    leaders.insert(target as usize);
+                }
+            }
+        }
+    }
+
+    let mut blocks = vec![];
+    let mut start = 0;
+    for (ip, op) in ops.iter().enumerate() {
+        if ip + 1 == len || leaders.contains(&(ip + 1)) {
+            blocks.push(Block {
+                start,
+                end: ip + 1,
+                targets: op.targets(ip),
+            });
+            start = ip + 1;
+        }
+    }
+
+    blocks
+}
+
+/// Tarjan's strongly connected components, with an explicit stack so long
+/// programs don't overflow the call stack. Only components that contain a
+/// cycle are returned.
+fn loops(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let len = successors.len();
+    let mut index = vec![usize::MAX; len];
+    let mut low_link = vec![0; len];
+    let mut on_stack = vec![false; len];
+    let mut component_stack = vec![];
+    let mut next_index = 0;
+    let mut loops = vec![];
+
+    for root in 0..len {
+        if index[root] != usize::MAX {
+            continue;
+        }
+
+        // NOTE: Each call stack entry is a node and the index of the next successor to visit.
+        let mut call_stack = vec![(root, 0)];
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        component_stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((node, next_successor)) = call_stack.last_mut() {
+            let node = *node;
+            if let Some(successor) = successors[node].get(*next_successor) {
+                *next_successor += 1;
+                let successor = *successor;
+
+                if index[successor] == usize::MAX {
+                    index[successor] = next_index;
+                    low_link[successor] = next_index;
+                    next_index += 1;
+                    component_stack.push(successor);
+                    on_stack[successor] = true;
+                    call_stack.push((successor, 0));
+                } else if on_stack[successor] {
+                    low_link[node] = low_link[node].min(index[successor]);
+                }
+
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some((parent, _)) = call_stack.last() {
+                low_link[*parent] = low_link[*parent].min(low_link[node]);
+            }
+
+            if low_link[node] == index[node] {
+                let mut component = vec![];
+                loop {
+                    let member = component_stack.pop().unwrap();
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+
+                if component.len() > 1 || successors[node].contains(&node) {
+                    component.sort_unstable();
+                    loops.push(component);
+                }
+            }
+        }
+    }
+
+    loops.sort();
+
+    loops
+}
+
+impl Analysis {
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+
+        let reachable = self.reachable.iter().filter(|r| **r).count();
+        s.push_str(&format!("{} instructions, {} reachable from the start\n", self.len, reachable));
+
+        let unreachable: Vec<usize> = (0..self.len).filter(|ip| !self.reachable[*ip]).collect();
+        s.push_str(&format!("unreachable: {}\n", ranges(&unreachable)));
+
+        s.push_str(&format!("\n{} basic blocks:\n", self.blocks.len()));
+        for block in &self.blocks {
+            let targets: Vec<String> = block.targets.iter().map(|t| self.describe(*t)).collect();
+            let reachable = if self.reachable[block.start] { "" } else { " (unreachable)" };
+            let addresses: Vec<usize> = (block.start..block.end).collect();
+            s.push_str(&format!("  {} -> {}{}\n", ranges(&addresses), targets.join(", "), reachable));
+        }
+
+        s.push_str(&format!("\n{} loops:\n", self.loops.len()));
+        for component in &self.loops {
+            let reachable = if self.reachable[component[0]] { "" } else { " (unreachable)" };
+            s.push_str(&format!("  {}{}\n", ranges(component), reachable));
+        }
+
+        s.push_str(&format!("\n{} jumps leave the program:\n", self.escaping_jumps.len()));
+        for (ip, target) in &self.escaping_jumps {
+            s.push_str(&format!("  {} -> {}\n", ip, target));
+        }
+
+        s
+    }
+
+    /// Writes the control-flow graph as Graphviz DOT, one node per basic block.
+    /// Unreachable blocks are drawn dashed.
+    pub fn to_dot(&self, ops: &[Op]) -> String {
+        let mut s = String::new();
+        s.push_str("digraph cfg {\n");
+        s.push_str("    node [shape=box, fontname=monospace];\n");
+        s.push_str("    end [shape=doublecircle];\n");
+        if !self.escaping_jumps.is_empty() {
+            s.push_str("    out_of_bounds [shape=octagon, label=\"out of bounds\"];\n");
+        }
+
+        for block in &self.blocks {
+            let mut label = String::new();
+            for (ip, op) in ops.iter().enumerate().take(block.end).skip(block.start) {
+                label.push_str(&format!("{}: {}\\l", ip, op));
+            }
+
+            let style = if self.reachable[block.start] { "" } else { ", style=dashed" };
+            s.push_str(&format!("    b{} [label=\"{}\"{}];\n", block.start, label, style));
+        }
+
+        for block in &self.blocks {
+            for target in &block.targets {
+                let node = if *target < 0 || *target > self.len as isize {
+                    String::from("out_of_bounds")
+                } else if *target == self.len as isize {
+                    String::from("end")
+                } else {
+                    format!("b{}", target)
+                };
+
+                s.push_str(&format!("    b{} -> {};\n", block.start, node));
+            }
+        }
+
+        s.push_str("}\n");
+
+        s
+    }
+
+    fn describe(&self, target: isize) -> String {
+        if target < 0 || target > self.len as isize {
+            format!("{} (out of bounds)", target)
+        } else if target == self.len as isize {
+            String::from("end")
+        } else {
+            target.to_string()
+        }
+    }
+}
+
+/// Formats sorted addresses compactly, e.g. `1..3, 7`.
+fn ranges(addresses: &[usize]) -> String {
+    if addresses.is_empty() {
+        return String::from("none");
+    }
+
+    let mut parts = vec![];
+    let mut start = addresses[0];
+    let mut previous = addresses[0];
+    for address in addresses.iter().skip(1).chain(std::iter::once(&usize::MAX)) {
+        if *address != previous + 1 || *address == usize::MAX {
+            if start == previous {
+                parts.push(start.to_string());
+            } else {
+                parts.push(format!("{}..{}", start, previous));
+            }
+            start = *address;
+        }
+        previous = *address;
+    }
+
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asm;
+    use crate::isa::Dialect;
+
+    #[test]
+    fn test_example() {
+        let ops = asm::assemble(include_str!("example_day8.txt"), Dialect::Boot).unwrap();
+        let analysis = analyze(&ops);
+
+        let unreachable: Vec<usize> = (0..ops.len()).filter(|ip| !analysis.reachable[*ip]).collect();
+        assert_eq!(unreachable, vec![5, 8]);
+
+        let blocks: Vec<(usize, usize, Vec<isize>)> = analysis
+            .blocks
+            .iter()
+            .map(|b| (b.start, b.end, b.targets.clone()))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0, 1, vec![1]),
+                (1, 3, vec![6]),
+                (3, 5, vec![1]),
+                (5, 6, vec![6]),
+                (6, 8, vec![3]),
+                (8, 9, vec![9]),
+            ]
+        );
+
+        assert_eq!(analysis.loops, vec![vec![1, 2, 3, 4, 6, 7]]);
+        assert!(analysis.escaping_jumps.is_empty());
+    }
+
+    #[test]
+    fn test_escaping_jumps_and_self_loops() {
+        let ops = vec![Op::Acc(1), Op::Jmp(0), Op::Jmp(-5), Op::Jmp(2)];
+        let analysis = analyze(&ops);
+
+        assert_eq!(analysis.escaping_jumps, vec![(2, -3), (3, 5)]);
+        assert_eq!(analysis.loops, vec![vec![1]]);
+        assert_eq!(ranges(&[2, 3]), "2..3");
+        assert!(analysis.report().contains("unreachable: 2..3\n"));
+    }
+
+    #[test]
+    fn test_dot() {
+        let ops = vec![Op::Acc(1), Op::Jmp(-1)];
+        let dot = analyze(&ops).to_dot(&ops);
+
+        assert!(dot.contains("    b0 [label=\"0: acc +1\\l1: jmp -1\\l\"];\n"));
+        assert!(dot.contains("    b0 -> b0;\n"));
+    }
+}
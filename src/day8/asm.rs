@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::isa::{self, Dialect, OperandKind, Op};
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+enum Operand<'a> {
+    Number(i32),
+    Label(&'a str),
+}
+
+struct Instruction<'a> {
+    line: usize,
+    address: usize,
+    mnemonic: &'a str,
+    operand: Operand<'a>,
+}
+
+/// Assembles boot code into ops, accepting only the instructions of `dialect`.
+///
+/// Each line holds at most one instruction, optionally preceded by one or
+/// more `label:` definitions. Operands are signed immediates with or without
+/// a `+` or registers `r0` to `r3`, and jumps may name a label instead,
+/// which is turned into the relative offset to it. Everything after `#` or
+/// `;` is a comment.
+pub fn assemble(source: &str, dialect: Dialect) -> Result<Vec<Op>, AsmError> {
+    let mut labels: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut instructions = vec![];
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| AsmError { line: line_number, message };
+
+        let mut rest = strip_comment(line).trim();
+        while let Some(colon) = rest.find(':') {
+            let label = rest[..colon].trim();
+            if !is_label(label) {
+                return Err(error(format!("invalid label '{}'", label)));
+            }
+
+            if let Some((_, first_line)) = labels.get(label) {
+                return Err(error(format!("label '{}' already defined on line {}", label, first_line)));
+            }
+
+            labels.insert(label, (instructions.len(), line_number));
+            rest = rest[colon + 1..].trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let parts: Vec<&str> = rest.split_whitespace().collect();
+        let mnemonic = parts[0];
+        let kind = match dialect.operand(mnemonic) {
+            Some(kind) => kind,
+            None if Dialect::Extended.operand(mnemonic).is_some() => {
+                return Err(error(format!("'{}' is not part of the boot dialect", mnemonic)))
+            }
+            None => return Err(error(format!("unknown instruction '{}'", mnemonic))),
+        };
+
+        let operand = match (kind, &parts[1..]) {
+            (OperandKind::None, []) => Operand::Number(0),
+            (OperandKind::None, [operand, ..]) => {
+                return Err(error(format!("'{}' takes no operand, got '{}'", mnemonic, operand)))
+            }
+            (_, []) => return Err(error(format!("missing operand for '{}'", mnemonic))),
+            (OperandKind::Offset, [operand]) if is_label(operand) => Operand::Label(operand),
+            (OperandKind::Register, [operand]) => Operand::Number(isa::parse_register(operand).map_err(error)?),
+            (_, [operand]) => Operand::Number(isa::parse_immediate(operand).map_err(error)?),
+            (_, [_, rest @ ..]) => {
+                return Err(error(format!("unexpected '{}' after operand", rest.join(" "))))
+            }
+        };
+
+        instructions.push(Instruction {
+            line: line_number,
+            address: instructions.len(),
+            mnemonic,
+            operand,
+        });
+    }
+
+    let mut ops = vec![];
+    for instruction in instructions {
+        let error = |message: String| AsmError { line: instruction.line, message };
+
+        let arg = match instruction.operand {
+            Operand::Number(arg) => arg,
+            Operand::Label(label) => match labels.get(label) {
+                Some((address, _)) => *address as i32 - instruction.address as i32,
+                None => return Err(error(format!("undefined label '{}'", label))),
+            },
+        };
+
+        ops.push(Op::from_parts(instruction.mnemonic, arg).unwrap());
+    }
+
+    Ok(ops)
+}
+
+/// Prints ops one per line with their address and, for jumps and `nop`,
+/// the address the jump would land on. The output assembles back to the same ops.
+pub fn disassemble(ops: &[Op]) -> String {
+    let mut s = String::new();
+
+    for (address, op) in ops.iter().enumerate() {
+        let instruction = op.to_string();
+        match op.jump_offset() {
+            None => s.push_str(&format!("{:<12}; {}\n", instruction, address)),
+            Some(offset) => {
+                let target = address as isize + offset as isize;
+                let target = if target < 0 || target > ops.len() as isize {
+                    format!("{} (out of bounds)", target)
+                } else if target == ops.len() as isize {
+                    format!("{} (end)", target)
+                } else {
+                    target.to_string()
+                };
+
+                s.push_str(&format!("{:<12}; {} -> {}\n", instruction, address, target));
+            }
+        }
+    }
+
+    s
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(['#', ';']) {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("example_day8.txt");
+
+    #[test]
+    fn test_example_round_trip() {
+        let ops = assemble(EXAMPLE, Dialect::Boot).unwrap();
+        assert_eq!(ops.len(), 9);
+        assert_eq!(ops[4], Op::Jmp(-3));
+
+        let disassembled = disassemble(&ops);
+        assert_eq!(assemble(&disassembled, Dialect::Boot), Ok(ops));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let ops = assemble(EXAMPLE, Dialect::Boot).unwrap();
+        let disassembled = disassemble(&ops);
+        let lines: Vec<&str> = disassembled.lines().collect();
+
+        assert_eq!(lines[0], "nop +0      ; 0 -> 0");
+        assert_eq!(lines[1], "acc +1      ; 1");
+        assert_eq!(lines[7], "jmp -4      ; 7 -> 3");
+    }
+
+    #[test]
+    fn test_labels_and_comments() {
+        let source = "\
+            # count to three\n\
+            start: acc 1   ; immediates without a sign\n\
+            \n\
+            loop:\n\
+            \tacc -1\n\
+            \tnop end\n\
+            \tjmp loop\n\
+            end:";
+
+        let ops = assemble(source, Dialect::Boot).unwrap();
+        assert_eq!(ops, vec![Op::Acc(1), Op::Acc(-1), Op::Nop(2), Op::Jmp(-2)]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = assemble("acc +1\nfoo +2", Dialect::Boot).unwrap_err();
+        assert_eq!(error, AsmError { line: 2, message: String::from("unknown instruction 'foo'") });
+
+        let error = assemble("acc +1\n\njmp nowhere", Dialect::Boot).unwrap_err();
+        assert_eq!(error.line, 3);
+        assert_eq!(error.to_string(), "line 3: undefined label 'nowhere'");
+
+        assert_eq!(assemble("x: acc +1\nx: nop +0", Dialect::Boot).unwrap_err().line, 2);
+        assert_eq!(assemble("acc", Dialect::Boot).unwrap_err().line, 1);
+        assert_eq!(assemble("acc +1 +2", Dialect::Boot).unwrap_err().line, 1);
+        assert_eq!(assemble("acc +x1", Dialect::Boot).unwrap_err().line, 1);
+        assert_eq!(assemble("here: acc here", Dialect::Boot).unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_dialects() {
+        let source = "\
+            acc 3\n\
+            loop: sta r0\n\
+            out\n\
+            acc -1\n\
+            jnz loop\n\
+            halt";
+
+        let ops = assemble(source, Dialect::Extended).unwrap();
+        assert_eq!(ops[1], Op::Sta(0));
+        assert_eq!(ops[4], Op::Jnz(-3));
+        assert_eq!(assemble(&disassemble(&ops), Dialect::Extended), Ok(ops));
+
+        let error = assemble(source, Dialect::Boot).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: 'sta' is not part of the boot dialect");
+        assert!(assemble("sta r9", Dialect::Extended).is_err());
+    }
+}
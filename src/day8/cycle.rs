@@ -0,0 +1,224 @@
+use crate::isa::{Dialect, Registers};
+use crate::{Outcome, VM};
+
+/// The number of steps `LoopPolicy::StepBudget`, and `LoopPolicy::Auto` when it
+/// picks `FullState`, allow when no step limit is set.
+pub const DEFAULT_STEP_BUDGET: usize = 1_000_000;
+
+/// How the VM decides that a program will never stop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoopPolicy {
+    /// `FirstRevisit` for boot code and `FullState` for anything else, in which
+    /// case it stops after `DEFAULT_STEP_BUDGET` steps when no limit is set.
+    Auto,
+    /// Stop as soon as any instruction is about to run a second time. Only
+    /// sound for boot code, whose control flow never depends on the registers.
+    FirstRevisit,
+    /// Stop when the instruction pointer and all registers repeat. Boot code
+    /// that keeps adding to the accumulator only repeats once it wraps around,
+    /// so this wants a step limit.
+    FullState,
+    /// Never report a loop, just stop at the step limit, or after
+    /// `DEFAULT_STEP_BUDGET` steps when none is set.
+    StepBudget,
+    /// Floyd's tortoise and hare, which reports where the cycle starts and how
+    /// long it is without remembering past states.
+    Floyd,
+    /// Brent's algorithm, like `Floyd` but with fewer steps.
+    Brent,
+}
+
+impl LoopPolicy {
+    pub fn from_name(name: &str) -> Option<LoopPolicy> {
+        match name {
+            "auto" => Some(LoopPolicy::Auto),
+            "first-revisit" => Some(LoopPolicy::FirstRevisit),
+            "full-state" => Some(LoopPolicy::FullState),
+            "budget" => Some(LoopPolicy::StepBudget),
+            "floyd" => Some(LoopPolicy::Floyd),
+            "brent" => Some(LoopPolicy::Brent),
+            _ => None,
+        }
+    }
+
+    /// Replaces `Auto` with the policy it stands for in `dialect`.
+    pub fn resolve(self, dialect: Dialect) -> LoopPolicy {
+        match (self, dialect) {
+            (LoopPolicy::Auto, Dialect::Boot) => LoopPolicy::FirstRevisit,
+            (LoopPolicy::Auto, Dialect::Extended) => LoopPolicy::FullState,
+            (policy, _) => policy,
+        }
+    }
+}
+
+/// The part of the state that decides where execution goes next. Boot code
+/// never branches on the accumulator, so its registers are left out, otherwise
+/// a loop that keeps adding to the accumulator would never repeat. A `halt`
+/// leaves the instruction pointer where it is, so that is included too.
+fn control_state(vm: &VM) -> (usize, bool, Option<Registers>) {
+    match vm.dialect {
+        Dialect::Boot => (vm.instruction_pointer, vm.halted, None),
+        Dialect::Extended => (vm.instruction_pointer, vm.halted, Some(vm.registers.clone())),
+    }
+}
+
+/// Runs `vm` until it stops without any loop detection. Used once a fork
+/// running ahead has stopped, so `vm` is known to stop too.
+fn finish(vm: &mut VM) -> Outcome {
+    loop {
+        if let Some(outcome) = vm.step() {
+            return outcome;
+        }
+    }
+}
+
+/// Floyd's cycle detection. The VM is left at the start of the cycle.
+pub fn floyd(vm: &mut VM) -> Outcome {
+    let start = vm.snapshot();
+    let mut hare = vm.fork();
+
+    // NOTE: The VM runs into the cycle twice, only the second run is traced.
+    let tracer = vm.set_tracer(None);
+
+    loop {
+        if hare.step().is_some() || hare.step().is_some() {
+            vm.set_tracer(tracer);
+            return finish(vm);
+        }
+
+        vm.step();
+        if control_state(vm) == control_state(&hare) {
+            break;
+        }
+    }
+
+    // NOTE: The hare is now a multiple of the cycle length ahead, so moving both
+    // one step at a time from the start and the meeting point meets at the entry.
+    // The cycle is known to exist by now, so the hare may go past the step limit.
+    hare.set_step_limit(None);
+    vm.restore(&start);
+    vm.set_tracer(tracer);
+    while control_state(vm) != control_state(&hare) {
+        vm.step();
+        hare.step();
+    }
+
+    let mut length = 1;
+    hare.step();
+    while control_state(vm) != control_state(&hare) {
+        hare.step();
+        length += 1;
+    }
+
+    cycle(vm, length)
+}
+
+/// Brent's cycle detection. The VM is left at the start of the cycle.
+pub fn brent(vm: &mut VM) -> Outcome {
+    let mut tortoise = vm.fork();
+    let mut hare = vm.fork();
+    let mut power = 1;
+    let mut length = 1;
+
+    if hare.step().is_some() {
+        return finish(vm);
+    }
+
+    while control_state(&tortoise) != control_state(&hare) {
+        if power == length {
+            tortoise = hare.fork();
+            power *= 2;
+            length = 0;
+        }
+
+        if hare.step().is_some() {
+            return finish(vm);
+        }
+        length += 1;
+    }
+
+    let mut hare = vm.fork();
+    hare.set_step_limit(None);
+    for _ in 0..length {
+        hare.step();
+    }
+
+    while control_state(vm) != control_state(&hare) {
+        vm.step();
+        hare.step();
+    }
+
+    cycle(vm, length)
+}
+
+fn cycle(vm: &VM, length: usize) -> Outcome {
+    Outcome::Cycle {
+        acc: vm.registers.acc,
+        entry: vm.instruction_pointer,
+        start: vm.steps,
+        length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asm;
+    use crate::isa::Op;
+
+    fn run(ops: Vec<Op>, policy: LoopPolicy) -> Outcome {
+        let mut vm = VM::new(ops);
+        vm.set_loop_policy(policy);
+
+        vm.run()
+    }
+
+    #[test]
+    fn test_boot_policies() {
+        let ops = asm::assemble(include_str!("example_day8.txt"), Dialect::Boot).unwrap();
+
+        let loop_at_1 = Outcome::InfiniteLoop { acc: 5, ip: 1 };
+        let cycle = Outcome::Cycle { acc: 0, entry: 1, start: 1, length: 6 };
+        assert_eq!(run(ops.clone(), LoopPolicy::Auto), loop_at_1);
+        assert_eq!(run(ops.clone(), LoopPolicy::FirstRevisit), loop_at_1);
+        assert_eq!(run(ops.clone(), LoopPolicy::Floyd), cycle);
+        assert_eq!(run(ops.clone(), LoopPolicy::Brent), cycle);
+
+        let mut vm = VM::new(ops);
+        vm.set_loop_policy(LoopPolicy::StepBudget);
+        vm.set_step_limit(Some(100));
+        assert_eq!(vm.run(), Outcome::StepLimit);
+        assert_eq!(vm.steps, 100);
+    }
+
+    #[test]
+    fn test_extended_policies() {
+        // NOTE: The accumulator alternates between 1 and -1, so instruction 1 is
+        // revisited after 2 steps but the whole state only after 4.
+        let ops = vec![Op::Acc(1), Op::Mul(-1), Op::Jmp(-1)];
+
+        let cycle = Outcome::Cycle { acc: 1, entry: 1, start: 1, length: 4 };
+        assert_eq!(run(ops.clone(), LoopPolicy::FirstRevisit), Outcome::InfiniteLoop { acc: -1, ip: 1 });
+        assert_eq!(run(ops.clone(), LoopPolicy::FullState), Outcome::InfiniteLoop { acc: 1, ip: 1 });
+        assert_eq!(run(ops.clone(), LoopPolicy::Floyd), cycle);
+        assert_eq!(run(ops, LoopPolicy::Brent), cycle);
+
+        // NOTE: The accumulator never repeats, so only the budget stops it.
+        let ops = asm::assemble("acc +1\nout\njmp -2", Dialect::Extended).unwrap();
+        let mut vm = VM::new(ops);
+        assert_eq!(vm.run(), Outcome::StepLimit);
+        assert_eq!(vm.steps, DEFAULT_STEP_BUDGET);
+    }
+
+    #[test]
+    fn test_stopping_programs() {
+        let ops = vec![Op::Acc(3), Op::Out, Op::Acc(-1), Op::Jnz(-2), Op::Halt];
+
+        for policy in [LoopPolicy::Floyd, LoopPolicy::Brent, LoopPolicy::StepBudget] {
+            assert_eq!(run(ops.clone(), policy), Outcome::Terminated { acc: 0 });
+            assert_eq!(run(vec![Op::Jmp(-1)], policy), Outcome::OutOfBounds { ip: -1 });
+            assert_eq!(run(vec![Op::Acc(1), Op::Halt], policy), Outcome::Terminated { acc: 1 });
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use crate::isa::{Dialect, Op, REGISTERS};
+use crate::{asm, Outcome, VM};
+
+const HELP: &str = "\
+step [n]             execute one (or n) instructions
+continue             run until a breakpoint, a watch or the end of the program
+break <addr|op>      break before an address or before any instance of an opcode
+delete <addr|op>     remove a breakpoint
+watch                toggle breaking whenever the accumulator changes
+regs                 print the registers
+visited              print the addresses executed so far
+list [addr]          disassemble around the instruction pointer (or addr)
+patch <addr> <op>    replace an instruction, e.g. `patch 7 nop -4` or `patch 3 out`
+flip <addr>          swap a jmp for a nop or the other way around
+reset                restart the program, keeping patches and breakpoints
+quit                 leave the debugger";
+
+pub struct Debugger {
+    vm: VM,
+    address_breakpoints: BTreeSet<usize>,
+    opcode_breakpoints: BTreeSet<String>,
+    watch_accumulator: bool,
+    outcome: Option<Outcome>,
+}
+
+impl Debugger {
+    pub fn new(vm: VM) -> Debugger {
+        Debugger {
+            vm,
+            address_breakpoints: BTreeSet::new(),
+            opcode_breakpoints: BTreeSet::new(),
+            watch_accumulator: false,
+            outcome: None,
+        }
+    }
+
+    /// Reads commands from `input` until it runs out or `quit` is given.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        write!(output, "(day8) ")?;
+        output.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            match parts.as_slice() {
+                [] => (),
+                ["q"] | ["quit"] => return Ok(()),
+                ["h"] | ["help"] => writeln!(output, "{}", HELP)?,
+                ["s"] | ["step"] => self.step(1, &mut output)?,
+                ["s", n] | ["step", n] => match n.parse() {
+                    Ok(n) => self.step(n, &mut output)?,
+                    Err(_) => writeln!(output, "invalid step count '{}'", n)?,
+                },
+                ["c"] | ["continue"] => self.continue_running(&mut output)?,
+                ["b", target] | ["break", target] => self.set_breakpoint(target, true, &mut output)?,
+                ["d", target] | ["delete", target] => self.set_breakpoint(target, false, &mut output)?,
+                ["w"] | ["watch"] => {
+                    self.watch_accumulator = !self.watch_accumulator;
+                    let state = if self.watch_accumulator { "on" } else { "off" };
+                    writeln!(output, "accumulator watch {}", state)?;
+                }
+                ["r"] | ["regs"] => self.print_registers(&mut output)?,
+                ["v"] | ["visited"] => {
+                    let visited: BTreeSet<&usize> = self.vm.completed_instructions.iter().collect();
+                    let visited: Vec<String> = visited.iter().map(|ip| ip.to_string()).collect();
+                    writeln!(output, "visited: {}", visited.join(" "))?;
+                }
+                ["l"] | ["list"] => self.list(self.vm.instruction_pointer, &mut output)?,
+                ["l", address] | ["list", address] => match address.parse() {
+                    Ok(address) => self.list(address, &mut output)?,
+                    Err(_) => writeln!(output, "invalid address '{}'", address)?,
+                },
+                ["p", address, instruction @ ..] | ["patch", address, instruction @ ..] => {
+                    self.patch(address, &instruction.join(" "), &mut output)?
+                }
+                ["f", address] | ["flip", address] => match self.address(address) {
+                    Some(address) => {
+                        self.vm.flip(address);
+                        self.list(address, &mut output)?;
+                    }
+                    None => writeln!(output, "invalid address '{}'", address)?,
+                },
+                ["reset"] => {
+                    self.vm.reset();
+                    self.outcome = None;
+                    self.print_registers(&mut output)?;
+                }
+                _ => writeln!(output, "unknown command '{}', try 'help'", line.trim())?,
+            }
+
+            write!(output, "(day8) ")?;
+            output.flush()?;
+        }
+
+        writeln!(output)
+    }
+
+    fn step<W: Write>(&mut self, n: usize, output: &mut W) -> io::Result<()> {
+        for _ in 0..n {
+            if !self.execute(output)? {
+                break;
+            }
+        }
+
+        self.print_registers(output)
+    }
+
+    fn continue_running<W: Write>(&mut self, output: &mut W) -> io::Result<()> {
+        loop {
+            let accumulator = self.vm.registers.acc;
+            if !self.execute(output)? {
+                return Ok(());
+            }
+
+            if self.watch_accumulator && self.vm.registers.acc != accumulator {
+                writeln!(output, "accumulator changed from {} to {}", accumulator, self.vm.registers.acc)?;
+                break;
+            }
+
+            let ip = self.vm.instruction_pointer;
+            if self.address_breakpoints.contains(&ip) {
+                writeln!(output, "breakpoint at {}", ip)?;
+                break;
+            }
+
+            if let Some(op) = self.vm.ops.get(ip) {
+                if self.opcode_breakpoints.contains(op.mnemonic()) {
+                    writeln!(output, "breakpoint on {} at {}", op.mnemonic(), ip)?;
+                    break;
+                }
+            }
+        }
+
+        self.print_registers(output)
+    }
+
+    /// Runs one instruction, returning false once the program has stopped.
+    fn execute<W: Write>(&mut self, output: &mut W) -> io::Result<bool> {
+        if self.outcome.is_none() {
+            self.outcome = self.vm.step();
+        }
+
+        match &self.outcome {
+            Some(outcome) => {
+                writeln!(output, "program stopped: {}", outcome)?;
+                Ok(false)
+            }
+            None => Ok(true),
+        }
+    }
+
+    fn set_breakpoint<W: Write>(&mut self, target: &str, set: bool, output: &mut W) -> io::Result<()> {
+        if let Ok(address) = target.parse::<usize>() {
+            if set {
+                self.address_breakpoints.insert(address);
+            } else {
+                self.address_breakpoints.remove(&address);
+            }
+        } else if Dialect::Extended.operand(target).is_some() {
+            if set {
+                self.opcode_breakpoints.insert(target.to_string());
+            } else {
+                self.opcode_breakpoints.remove(target);
+            }
+        } else {
+            return writeln!(output, "expected an address or an opcode, got '{}'", target);
+        }
+
+        let addresses: Vec<String> = self.address_breakpoints.iter().map(|a| a.to_string()).collect();
+        let opcodes: Vec<&str> = self.opcode_breakpoints.iter().map(|o| o.as_str()).collect();
+        writeln!(output, "breakpoints: {}", [addresses.join(" "), opcodes.join(" ")].join(" ").trim())
+    }
+
+    fn patch<W: Write>(&mut self, address: &str, instruction: &str, output: &mut W) -> io::Result<()> {
+        let address = match self.address(address) {
+            Some(address) => address,
+            None => return writeln!(output, "invalid address '{}'", address),
+        };
+
+        match Op::parse(instruction, Dialect::Extended) {
+            Ok(op) => {
+                self.vm.patch(address, op);
+                self.list(address, output)
+            }
+            Err(message) => writeln!(output, "{}", message),
+        }
+    }
+
+    fn print_registers<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        writeln!(
+            output,
+            "ip: {} acc: {} steps: {}",
+            self.vm.instruction_pointer, self.vm.registers.acc, self.vm.steps
+        )?;
+
+        // NOTE: Boot code never touches the other registers, so they're only shown once used.
+        if self.vm.registers.general != [0; REGISTERS] || !self.vm.output.is_empty() {
+            let general: Vec<String> = self.vm.registers.general.iter().map(|r| r.to_string()).collect();
+            let printed: Vec<String> = self.vm.output.iter().map(|n| n.to_string()).collect();
+            writeln!(output, "r: {} output: {}", general.join(" "), printed.join(" "))?;
+        }
+
+        Ok(())
+    }
+
+    fn list<W: Write>(&self, address: usize, output: &mut W) -> io::Result<()> {
+        let disassembled = asm::disassemble(&self.vm.ops);
+        let start = address.saturating_sub(3);
+
+        for (i, line) in disassembled.lines().enumerate().skip(start).take(7) {
+            let marker = if i == self.vm.instruction_pointer { "=>" } else { "  " };
+            let breakpoint = if self.address_breakpoints.contains(&i) { "*" } else { " " };
+            writeln!(output, "{}{} {}", marker, breakpoint, line)?;
+        }
+
+        Ok(())
+    }
+
+    fn address(&self, s: &str) -> Option<usize> {
+        match s.parse() {
+            Ok(address) if address < self.vm.ops.len() => Some(address),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debug(commands: &str) -> String {
+        let ops = asm::assemble(include_str!("example_day8.txt"), Dialect::Boot).unwrap();
+        let mut debugger = Debugger::new(VM::new(ops));
+
+        let mut output = vec![];
+        debugger.run(commands.as_bytes(), &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_step_and_breakpoints() {
+        let output = debug("step 2\nbreak 4\ncontinue\nvisited\n");
+
+        assert!(output.contains("ip: 2 acc: 1 steps: 2"));
+        assert!(output.contains("breakpoint at 4\nip: 4 acc: 5 steps: 6"));
+        assert!(output.contains("visited: 0 1 2 3 6 7"));
+    }
+
+    #[test]
+    fn test_opcode_breakpoints() {
+        let output = debug("break jmp\ncontinue\ncontinue\n");
+
+        assert!(output.contains("breakpoint on jmp at 2\nip: 2 acc: 1 steps: 2"));
+        assert!(output.contains("breakpoint on jmp at 7\nip: 7 acc: 2 steps: 4"));
+    }
+
+    #[test]
+    fn test_watch() {
+        let output = debug("watch\ncontinue\ncontinue\n");
+
+        assert!(output.contains("accumulator changed from 0 to 1\nip: 2 acc: 1 steps: 2"));
+        assert!(output.contains("accumulator changed from 1 to 2\nip: 7 acc: 2 steps: 4"));
+    }
+
+    #[test]
+    fn test_patch_and_continue() {
+        let output = debug("patch 7 nop -4\ncontinue\nreset\nflip 7\ncontinue\n");
+
+        assert!(output.contains("    nop -4      ; 7 -> 3"));
+        assert!(output.contains("program stopped: terminated with accumulator 8"));
+        assert!(output.contains("program stopped: infinite loop at 1 with accumulator 5"));
+    }
+
+    #[test]
+    fn test_extended_patch() {
+        let output = debug("patch 2 sta r1\nbreak out\npatch 3 out\ncontinue\nstep\n");
+
+        assert!(output.contains("    sta r1      ; 2"));
+        assert!(output.contains("breakpoint on out at 3\nip: 3 acc: 1 steps: 3\nr: 0 1 0 0 output: \n"));
+        assert!(output.contains("ip: 4 acc: 1 steps: 4\nr: 0 1 0 0 output: 1\n"));
+    }
+}
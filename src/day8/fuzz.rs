@@ -0,0 +1,257 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::cycle::LoopPolicy;
+use crate::isa::Op;
+use crate::repair::{self, Repair};
+use crate::{asm, dialect_of, Outcome, VM};
+
+/// Steps each run in `check` may take, so random extended programs that
+/// count for a long time don't stall the harness.
+const STEP_LIMIT: usize = 10_000;
+
+/// Programs `corrupted_program` generates before giving up on a seed.
+const CORRUPTION_ATTEMPTS: usize = 1000;
+
+/// A small xorshift generator, so runs can be reproduced from their seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // NOTE: Xorshift never leaves zero, so mix the seed into a non-zero state.
+        Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// A number in `low..=high`.
+    pub fn between(&mut self, low: i32, high: i32) -> i32 {
+        low + self.below((high - low) as usize + 1) as i32
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// Any boot or extended program, with jumps that may leave it.
+pub fn random_program(rng: &mut Rng, len: usize) -> Vec<Op> {
+    let reach = len as i32 + 2;
+    (0..len)
+        .map(|_| match rng.below(11) {
+            0 => Op::Nop(rng.between(-reach, reach)),
+            1 => Op::Acc(rng.between(-100, 100)),
+            2 => Op::Jmp(rng.between(-reach, reach)),
+            3 => Op::Jz(rng.between(-reach, reach)),
+            4 => Op::Jnz(rng.between(-reach, reach)),
+            5 => Op::Mul(rng.between(-3, 3)),
+            6 => Op::Sta(rng.below(4)),
+            7 => Op::Lda(rng.below(4)),
+            8 => Op::Add(rng.below(4)),
+            9 => Op::Out,
+            _ => Op::Halt,
+        })
+        .collect()
+}
+
+/// Boot code that terminates, along with the addresses it runs in order.
+///
+/// A random selection of addresses is chained into the execution path, each
+/// one continuing at the next with an `acc` or `nop` when they are adjacent and
+/// a `jmp` otherwise, and the last one jumping to the end. Everything off the
+/// path is filler that jumps anywhere inside the program.
+pub fn terminating_program(rng: &mut Rng, len: usize) -> (Vec<Op>, Vec<usize>) {
+    let len = len.max(1);
+
+    let mut rest: Vec<usize> = (1..len).collect();
+    rng.shuffle(&mut rest);
+    rest.truncate(rng.below(len));
+
+    let mut path = vec![0];
+    path.extend(rest);
+
+    let mut ops: Vec<Op> = (0..len)
+        .map(|ip| {
+            let target = rng.below(len) as i32 - ip as i32;
+            match rng.below(3) {
+                0 => Op::Nop(target),
+                1 => Op::Acc(rng.between(-100, 100)),
+                _ => Op::Jmp(target),
+            }
+        })
+        .collect();
+
+    for (i, ip) in path.iter().enumerate() {
+        let next = path.get(i + 1).copied().unwrap_or(len);
+        ops[*ip] = if next == ip + 1 {
+            match rng.below(2) {
+                0 => Op::Acc(rng.between(-100, 100)),
+                _ => Op::Nop(rng.below(len) as i32 - *ip as i32),
+            }
+        } else {
+            Op::Jmp(next as i32 - *ip as i32)
+        };
+    }
+
+    (ops, path)
+}
+
+/// Boot code with exactly one instruction corrupted, returned with the
+/// corrupted address. Flipping that instruction back is the only repair.
+///
+/// The corruption turns a `nop` on the path of a terminating program into a
+/// `jmp` back to an address the path has already visited, so the corrupted
+/// program always loops. Programs where another flip would also make it
+/// terminate are thrown away, and after `CORRUPTION_ATTEMPTS` of them this
+/// gives up.
+pub fn corrupted_program(rng: &mut Rng, len: usize) -> Option<(Vec<Op>, usize)> {
+    for _ in 0..CORRUPTION_ATTEMPTS {
+        let (mut ops, path) = terminating_program(rng, len.max(2));
+
+        let nops: Vec<usize> = (0..path.len())
+            .filter(|i| matches!(ops[path[*i]], Op::Nop(_)))
+            .filter(|i| path.get(i + 1).copied().unwrap_or(ops.len()) == path[*i] + 1)
+            .collect();
+        if nops.is_empty() {
+            continue;
+        }
+
+        let i = nops[rng.below(nops.len())];
+        let ip = path[i];
+        let earlier = path[rng.below(i + 1)];
+
+        ops[ip] = Op::Jmp(earlier as i32 - ip as i32);
+
+        if let Repair::Unique { instruction, .. } = repair::repair(&ops) {
+            if instruction == ip {
+                return Some((ops, ip));
+            }
+        }
+    }
+
+    None
+}
+
+/// Checks the invariants every program must satisfy: no loop policy makes
+/// `run` panic, the detecting policies agree on whether it stops, any repair
+/// that's offered really terminates, and the program survives a round trip
+/// through the disassembler.
+pub fn check(ops: &[Op]) -> Result<(), String> {
+    let policies = [
+        LoopPolicy::Auto,
+        LoopPolicy::FullState,
+        LoopPolicy::StepBudget,
+        LoopPolicy::Floyd,
+        LoopPolicy::Brent,
+    ];
+
+    let mut stops = vec![];
+    for policy in policies {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut vm = VM::new(ops.to_vec());
+            vm.set_step_limit(Some(STEP_LIMIT));
+            vm.set_loop_policy(policy);
+            vm.run()
+        }))
+        .map_err(|_| format!("run panicked with the {:?} policy", policy))?;
+
+        let stopped = matches!(outcome, Outcome::Terminated { .. } | Outcome::OutOfBounds { .. });
+        if policy != LoopPolicy::StepBudget && outcome != Outcome::StepLimit {
+            stops.push((policy, stopped));
+        }
+    }
+
+    if let Some((policy, _)) = stops.iter().find(|(_, stopped)| *stopped != stops[0].1) {
+        return Err(format!("{:?} and {:?} disagree on whether the program stops", stops[0].0, policy));
+    }
+
+    let flips = match repair::repair(ops) {
+        Repair::NotNeeded { .. } => vec![],
+        Repair::Unique { instruction, .. } => vec![instruction],
+        Repair::Multiple { instructions } => instructions,
+        Repair::Impossible | Repair::Unsupported { .. } => vec![],
+    };
+    for instruction in flips {
+        let mut vm = VM::new(ops.to_vec());
+        vm.flip(instruction);
+        match vm.run() {
+            Outcome::Terminated { .. } => (),
+            outcome => return Err(format!("flipping {} was offered as a repair but {}", instruction, outcome)),
+        }
+    }
+
+    match asm::assemble(&asm::disassemble(ops), dialect_of(ops)) {
+        Ok(reassembled) if reassembled == ops => Ok(()),
+        Ok(_) => Err(String::from("the disassembly assembles to a different program")),
+        Err(error) => Err(format!("the disassembly doesn't assemble: {}", error)),
+    }
+}
+
+/// Checks `iterations` random programs of each kind, starting at `seed`, and
+/// returns the seed and program of the first one that fails. Otherwise
+/// returns how many seeds had no corrupted program to check.
+pub fn fuzz(seed: u64, iterations: u64, max_len: usize) -> Result<u64, (u64, Vec<Op>, String)> {
+    let mut uncorrupted = 0;
+    for seed in (0..iterations).map(|i| seed.wrapping_add(i)) {
+        let mut rng = Rng::new(seed);
+        let len = 1 + rng.below(max_len.max(1));
+
+        let corrupted = corrupted_program(&mut rng, len).map(|(ops, _)| ops);
+        if corrupted.is_none() {
+            uncorrupted += 1;
+        }
+        let programs = vec![Some(random_program(&mut rng, len)), Some(terminating_program(&mut rng, len).0), corrupted];
+
+        for ops in programs.into_iter().flatten() {
+            if let Err(message) = check(&ops) {
+                return Err((seed, ops, message));
+            }
+        }
+    }
+
+    Ok(uncorrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generators() {
+        for seed in 0..200 {
+            let mut rng = Rng::new(seed);
+
+            let (ops, _) = terminating_program(&mut rng, 1 + seed as usize % 30);
+            assert!(matches!(VM::new(ops.clone()).run(), Outcome::Terminated { .. }));
+            assert!(matches!(repair::repair(&ops), Repair::NotNeeded { .. }));
+
+            let (ops, corrupted) = corrupted_program(&mut rng, 2 + seed as usize % 30).unwrap();
+            assert!(matches!(VM::new(ops.clone()).run(), Outcome::InfiniteLoop { .. }));
+            match repair::repair(&ops) {
+                Repair::Unique { instruction, .. } => assert_eq!(instruction, corrupted),
+                repair => panic!("seed {}: {}", seed, repair),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz() {
+        if let Err((seed, ops, message)) = fuzz(0, 300, 40) {
+            panic!("seed {}: {}\n{}", seed, message, asm::disassemble(&ops));
+        }
+
+        // NOTE: The seeds wrap around rather than overflow.
+        assert!(fuzz(u64::MAX - 2, 5, 10).is_ok());
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+
+/// Number of general purpose registers, `r0` to `r3`, next to the accumulator.
+pub const REGISTERS: usize = 4;
+
+/// Which instructions a program may use. The original boot code only knows
+/// `nop`, `acc` and `jmp`; the extended dialect adds the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dialect {
+    Boot,
+    Extended,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperandKind {
+    None,
+    Immediate,
+    /// A relative jump, which the assembler also accepts as a label.
+    Offset,
+    Register,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Nop(i32),
+    Acc(i32),
+    Jmp(i32),
+    /// Jumps when the accumulator is zero.
+    Jz(i32),
+    /// Jumps when the accumulator is not zero.
+    Jnz(i32),
+    Mul(i32),
+    /// Stores the accumulator in a register.
+    Sta(usize),
+    /// Loads a register into the accumulator.
+    Lda(usize),
+    /// Adds a register to the accumulator.
+    Add(usize),
+    /// Appends the accumulator to the output.
+    Out,
+    Halt,
+}
+
+/// What happens after an op has run.
+#[derive(Debug, PartialEq)]
+pub enum Next {
+    /// Continue at the instruction this far from the current one.
+    Offset(i32),
+    Halt,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Registers {
+    pub acc: i32,
+    pub general: [i32; REGISTERS],
+}
+
+/// Every mnemonic with its operand and the dialect it first appears in.
+const INSTRUCTIONS: [(&str, OperandKind, Dialect); 11] = [
+    ("nop", OperandKind::Offset, Dialect::Boot),
+    ("acc", OperandKind::Immediate, Dialect::Boot),
+    ("jmp", OperandKind::Offset, Dialect::Boot),
+    ("jz", OperandKind::Offset, Dialect::Extended),
+    ("jnz", OperandKind::Offset, Dialect::Extended),
+    ("mul", OperandKind::Immediate, Dialect::Extended),
+    ("sta", OperandKind::Register, Dialect::Extended),
+    ("lda", OperandKind::Register, Dialect::Extended),
+    ("add", OperandKind::Register, Dialect::Extended),
+    ("out", OperandKind::None, Dialect::Extended),
+    ("halt", OperandKind::None, Dialect::Extended),
+];
+
+impl Dialect {
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        match name {
+            "boot" => Some(Dialect::Boot),
+            "extended" => Some(Dialect::Extended),
+            _ => None,
+        }
+    }
+
+    /// Whether programs in this dialect may use instructions from `other`.
+    pub fn includes(&self, other: Dialect) -> bool {
+        *self == Dialect::Extended || other == Dialect::Boot
+    }
+
+    /// The operand `mnemonic` takes, or `None` if this dialect doesn't have it.
+    pub fn operand(&self, mnemonic: &str) -> Option<OperandKind> {
+        INSTRUCTIONS
+            .iter()
+            .find(|(name, _, dialect)| *name == mnemonic && self.includes(*dialect))
+            .map(|(_, operand, _)| *operand)
+    }
+}
+
+impl Op {
+    /// Builds an op from its mnemonic and an already resolved operand. Registers
+    /// are passed as their number and ignored for ops without an operand.
+    pub fn from_parts(mnemonic: &str, arg: i32) -> Option<Op> {
+        let register = || if (0..REGISTERS as i32).contains(&arg) { Some(arg as usize) } else { None };
+
+        match mnemonic {
+            "nop" => Some(Op::Nop(arg)),
+            "acc" => Some(Op::Acc(arg)),
+            "jmp" => Some(Op::Jmp(arg)),
+            "jz" => Some(Op::Jz(arg)),
+            "jnz" => Some(Op::Jnz(arg)),
+            "mul" => Some(Op::Mul(arg)),
+            "sta" => register().map(Op::Sta),
+            "lda" => register().map(Op::Lda),
+            "add" => register().map(Op::Add),
+            "out" => Some(Op::Out),
+            "halt" => Some(Op::Halt),
+            _ => None,
+        }
+    }
+
+    /// Parses a single instruction with a numeric operand, e.g. `jmp -4` or `sta r1`.
+    pub fn parse(s: &str, dialect: Dialect) -> Result<Op, String> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        let mnemonic = match parts.first() {
+            Some(mnemonic) => *mnemonic,
+            None => return Err(String::from("missing instruction")),
+        };
+
+        let kind = match dialect.operand(mnemonic) {
+            Some(kind) => kind,
+            None => return Err(format!("unknown instruction '{}'", mnemonic)),
+        };
+
+        let arg = match (kind, &parts[1..]) {
+            (OperandKind::None, []) => 0,
+            (OperandKind::None, [operand, ..]) => {
+                return Err(format!("'{}' takes no operand, got '{}'", mnemonic, operand))
+            }
+            (_, []) => return Err(format!("missing operand for '{}'", mnemonic)),
+            (OperandKind::Register, [operand]) => parse_register(operand)?,
+            (_, [operand]) => parse_immediate(operand)?,
+            (_, [_, rest @ ..]) => return Err(format!("unexpected '{}' after operand", rest.join(" "))),
+        };
+
+        Op::from_parts(mnemonic, arg).ok_or_else(|| format!("invalid operand for '{}'", mnemonic))
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Op::Nop(_) => "nop",
+            Op::Acc(_) => "acc",
+            Op::Jmp(_) => "jmp",
+            Op::Jz(_) => "jz",
+            Op::Jnz(_) => "jnz",
+            Op::Mul(_) => "mul",
+            Op::Sta(_) => "sta",
+            Op::Lda(_) => "lda",
+            Op::Add(_) => "add",
+            Op::Out => "out",
+            Op::Halt => "halt",
+        }
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            Op::Nop(_) | Op::Acc(_) | Op::Jmp(_) => Dialect::Boot,
+            _ => Dialect::Extended,
+        }
+    }
+
+    /// The relative jump encoded in the op, including the one a `nop` would
+    /// make if it were flipped.
+    pub fn jump_offset(&self) -> Option<i32> {
+        match self {
+            Op::Nop(arg) | Op::Jmp(arg) | Op::Jz(arg) | Op::Jnz(arg) => Some(*arg),
+            _ => None,
+        }
+    }
+
+    /// Swaps `jmp` and `nop`, the only corruption boot code repair knows about.
+    pub fn flipped(&self) -> Option<Op> {
+        match self {
+            Op::Nop(arg) => Some(Op::Jmp(*arg)),
+            Op::Jmp(arg) => Some(Op::Nop(*arg)),
+            _ => None,
+        }
+    }
+
+    /// Where execution may continue after running this op at `ip`. The
+    /// addresses aren't checked, so they may lie outside the program.
+    pub fn targets(&self, ip: usize) -> Vec<isize> {
+        let next = ip as isize + 1;
+        match self {
+            Op::Jmp(arg) => vec![ip as isize + *arg as isize],
+            Op::Jz(arg) | Op::Jnz(arg) => vec![next, ip as isize + *arg as isize],
+            Op::Halt => vec![],
+            _ => vec![next],
+        }
+    }
+
+    /// Runs the op. Arithmetic wraps around rather than overflowing.
+    pub fn execute(&self, registers: &mut Registers, output: &mut Vec<i32>) -> Next {
+        match self {
+            Op::Nop(_) => (),
+            Op::Acc(arg) => registers.acc = registers.acc.wrapping_add(*arg),
+            Op::Jmp(arg) => return Next::Offset(*arg),
+            Op::Jz(arg) if registers.acc == 0 => return Next::Offset(*arg),
+            Op::Jnz(arg) if registers.acc != 0 => return Next::Offset(*arg),
+            Op::Jz(_) | Op::Jnz(_) => (),
+            Op::Mul(arg) => registers.acc = registers.acc.wrapping_mul(*arg),
+            Op::Sta(r) => registers.general[*r] = registers.acc,
+            Op::Lda(r) => registers.acc = registers.general[*r],
+            Op::Add(r) => registers.acc = registers.acc.wrapping_add(registers.general[*r]),
+            Op::Out => output.push(registers.acc),
+            Op::Halt => return Next::Halt,
+        }
+
+        Next::Offset(1)
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op::Nop(arg) | Op::Acc(arg) | Op::Jmp(arg) | Op::Jz(arg) | Op::Jnz(arg) | Op::Mul(arg) => {
+                write!(f, "{} {:+}", self.mnemonic(), arg)
+            }
+            Op::Sta(r) | Op::Lda(r) | Op::Add(r) => write!(f, "{} r{}", self.mnemonic(), r),
+            Op::Out | Op::Halt => write!(f, "{}", self.mnemonic()),
+        }
+    }
+}
+
+/// Parses a signed immediate, with or without a leading `+`.
+pub fn parse_immediate(s: &str) -> Result<i32, String> {
+    s.parse().map_err(|_| format!("invalid operand '{}'", s))
+}
+
+pub fn parse_register(s: &str) -> Result<i32, String> {
+    match s.strip_prefix('r').and_then(|n| n.parse::<usize>().ok()) {
+        Some(r) if r < REGISTERS => Ok(r as i32),
+        _ => Err(format!("invalid register '{}', expected r0 to r{}", s, REGISTERS - 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        for s in ["nop +0", "acc -99", "jz +3", "jnz -2", "mul +7", "sta r1", "lda r3", "add r0", "out", "halt"] {
+            let op = Op::parse(s, Dialect::Extended).unwrap();
+            assert_eq!(op.to_string(), s);
+        }
+
+        assert_eq!(Op::parse("acc 5", Dialect::Boot), Ok(Op::Acc(5)));
+        assert!(Op::parse("jz +3", Dialect::Boot).is_err());
+        assert!(Op::parse("sta r4", Dialect::Extended).is_err());
+        assert!(Op::parse("out +1", Dialect::Extended).is_err());
+        assert!(Op::parse("acc ++1", Dialect::Boot).is_err());
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut registers = Registers::default();
+        let mut output = vec![];
+
+        assert_eq!(Op::Acc(6).execute(&mut registers, &mut output), Next::Offset(1));
+        assert_eq!(Op::Mul(7).execute(&mut registers, &mut output), Next::Offset(1));
+        assert_eq!(Op::Sta(2).execute(&mut registers, &mut output), Next::Offset(1));
+        assert_eq!(Op::Add(2).execute(&mut registers, &mut output), Next::Offset(1));
+        assert_eq!(Op::Out.execute(&mut registers, &mut output), Next::Offset(1));
+        assert_eq!(Op::Jz(5).execute(&mut registers, &mut output), Next::Offset(1));
+        assert_eq!(Op::Jnz(5).execute(&mut registers, &mut output), Next::Offset(5));
+        assert_eq!(Op::Halt.execute(&mut registers, &mut output), Next::Halt);
+
+        assert_eq!(registers.acc, 84);
+        assert_eq!(registers.general, [0, 0, 42, 0]);
+        assert_eq!(output, vec![84]);
+
+        registers.acc = i32::MAX;
+        Op::Acc(1).execute(&mut registers, &mut output);
+        assert_eq!(registers.acc, i32::MIN);
+    }
+}
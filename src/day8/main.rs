@@ -1,172 +1,727 @@
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{self, BufReader};
+use std::io::{self};
 use std::collections::HashSet;
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+
+mod analysis;
+mod asm;
+mod cycle;
+mod debugger;
+mod fuzz;
+mod isa;
+mod repair;
+mod trace;
+
+use cycle::LoopPolicy;
+use debugger::Debugger;
+use isa::{Dialect, Next, Op, Registers};
+use trace::{Step, Tracer};
+
+/// Why `VM::run` stopped.
+#[derive(Debug, Clone, PartialEq)]
+enum Outcome {
+    /// The instruction pointer landed exactly one past the last instruction,
+    /// or a `halt` ran.
+    Terminated { acc: i32 },
+    /// The instruction at `ip` was about to run a second time. Programs using
+    /// the extended dialect may branch on the accumulator, so for them the
+    /// registers must also be the same as on the earlier visit.
+    InfiniteLoop { acc: i32, ip: usize },
+    /// Floyd's or Brent's algorithm found a cycle of `length` steps, which the
+    /// program enters at instruction `entry` after `start` steps.
+    Cycle { acc: i32, entry: usize, start: usize, length: usize },
+    /// A jump went before the first or more than one past the last instruction.
+    OutOfBounds { ip: isize },
+    /// The step limit was reached before the program stopped.
+    StepLimit,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Terminated { acc } => write!(f, "terminated with accumulator {}", acc),
+            Outcome::InfiniteLoop { acc, ip } => {
+                write!(f, "infinite loop at {} with accumulator {}", ip, acc)
+            }
+            Outcome::Cycle { acc, entry, start, length } => write!(
+                f,
+                "cycle of {} steps entered at {} on step {} with accumulator {}",
+                length, entry, start, acc
+            ),
+            Outcome::OutOfBounds { ip } => write!(f, "jumped out of bounds to {}", ip),
+            Outcome::StepLimit => write!(f, "step limit reached"),
+        }
+    }
+}
 
+/// Everything `VM::step` changes, saved by `VM::snapshot`.
 #[derive(Debug, Clone)]
-enum Op {
-    Nop(i32),
-    Acc(i32),
-    Jmp(i32),
+struct Snapshot {
+    completed_instructions: HashSet<usize>,
+    completed_states: HashSet<(usize, Registers)>,
+    instruction_pointer: usize,
+    registers: Registers,
+    output: Vec<i32>,
+    halted: bool,
+    steps: usize,
+    dialect: Dialect,
+    ops: Arc<Vec<Op>>,
 }
 
 #[derive(Debug)]
 struct VM {
-    did_break_on_inf_loop: bool,
     completed_instructions: HashSet<usize>,
+    completed_states: HashSet<(usize, Registers)>,
     instruction_pointer: usize,
-    accumulator: i32,
-    ops: Vec<Op>,
+    registers: Registers,
+    output: Vec<i32>,
+    halted: bool,
+    steps: usize,
+    step_limit: Option<usize>,
+    loop_policy: LoopPolicy,
+    tracer: Option<Tracer>,
+    dialect: Dialect,
+    /// Shared with forks and snapshots until one of them patches it.
+    ops: Arc<Vec<Op>>,
 }
 
 impl VM {
     fn new(ops: Vec<Op>) -> VM {
         VM {
-            did_break_on_inf_loop: false,
             completed_instructions: HashSet::new(),
+            completed_states: HashSet::new(),
             instruction_pointer: 0,
-            accumulator: 0,
-            ops: ops,
+            registers: Registers::default(),
+            output: vec![],
+            halted: false,
+            steps: 0,
+            step_limit: None,
+            loop_policy: LoopPolicy::Auto,
+            tracer: None,
+            dialect: dialect_of(&ops),
+            ops: Arc::new(ops),
         }
     }
 
-    fn get_accumulator(&self) -> i32 {
-        self.accumulator
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            completed_instructions: self.completed_instructions.clone(),
+            completed_states: self.completed_states.clone(),
+            instruction_pointer: self.instruction_pointer,
+            registers: self.registers.clone(),
+            output: self.output.clone(),
+            halted: self.halted,
+            steps: self.steps,
+            dialect: self.dialect,
+            ops: Arc::clone(&self.ops),
+        }
+    }
+
+    /// Returns to a snapshot, including the program as it was then. The step
+    /// limit, the loop policy and the tracer are kept.
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let snapshot = snapshot.clone();
+        self.completed_instructions = snapshot.completed_instructions;
+        self.completed_states = snapshot.completed_states;
+        self.instruction_pointer = snapshot.instruction_pointer;
+        self.registers = snapshot.registers;
+        self.output = snapshot.output;
+        self.halted = snapshot.halted;
+        self.steps = snapshot.steps;
+        self.dialect = snapshot.dialect;
+        self.ops = snapshot.ops;
     }
 
-    fn run(&mut self) {
-        while self.instruction_pointer != self.ops.len() - 1 {
-            let op = &self.ops[self.instruction_pointer];
+    /// Creates an independent VM that continues from the current state. The
+    /// program is only copied once either side patches it. Forks have the same
+    /// step limit and loop policy but no tracer.
+    fn fork(&self) -> VM {
+        let mut vm = VM::new(vec![]);
+        vm.set_step_limit(self.step_limit);
+        vm.set_loop_policy(self.loop_policy);
+        vm.restore(&self.snapshot());
 
-            if self.completed_instructions.contains(&self.instruction_pointer) {
-                self.did_break_on_inf_loop = true;
-                return;
-            }
+        vm
+    }
+
+    fn set_step_limit(&mut self, step_limit: Option<usize>) {
+        self.step_limit = step_limit;
+    }
 
-            self.completed_instructions.insert(self.instruction_pointer);
+    fn set_loop_policy(&mut self, loop_policy: LoopPolicy) {
+        self.loop_policy = loop_policy;
+    }
+
+    /// Attaches a tracer that records every executed step, returning the previous one.
+    fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.tracer, tracer)
+    }
+
+    fn reset(&mut self) {
+        self.completed_instructions = HashSet::new();
+        self.completed_states = HashSet::new();
+        self.instruction_pointer = 0;
+        self.registers = Registers::default();
+        self.output = vec![];
+        self.halted = false;
+        self.steps = 0;
+    }
+
+    /// Replaces the instruction at `address`.
+    fn patch(&mut self, address: usize, op: Op) {
+        Arc::make_mut(&mut self.ops)[address] = op;
+        self.dialect = dialect_of(&self.ops);
+    }
 
-            match op {
-                Op::Nop(_) => self.nop(),
-                Op::Acc(arg) => self.acc(*arg),
-                Op::Jmp(arg) => self.jmp(*arg),
+    fn run(&mut self) -> Outcome {
+        match self.loop_policy {
+            LoopPolicy::Floyd => return cycle::floyd(self),
+            LoopPolicy::Brent => return cycle::brent(self),
+            _ => (),
+        }
+
+        loop {
+            if let Some(outcome) = self.step() {
+                return outcome;
             }
         }
     }
 
-    fn try_repair_code(&mut self) {
-        let instructions = self.find_jumps_and_nops();
+    /// Executes a single instruction, returning the outcome once the program has
+    /// stopped. Floyd's and Brent's algorithms only run as part of `run`, so
+    /// single steps never report a loop under those policies.
+    fn step(&mut self) -> Option<Outcome> {
+        if self.halted || self.instruction_pointer == self.ops.len() {
+            return Some(Outcome::Terminated { acc: self.registers.acc });
+        }
 
-        for instruction in instructions {
-            {
-                let op = self.ops.get_mut(instruction).unwrap();
-                // NOTE (Emil): Convert instruction.
-                match op {
-                    Op::Nop(arg) => *op = Op::Jmp(*arg),
-                    Op::Jmp(arg) => *op = Op::Nop(*arg),
-                    _ => (),
-                }
-            }
+        let policy = self.loop_policy.resolve(self.dialect);
+        let revisited = match policy {
+            LoopPolicy::FirstRevisit => self.completed_instructions.contains(&self.instruction_pointer),
+            LoopPolicy::FullState => self
+                .completed_states
+                .contains(&(self.instruction_pointer, self.registers.clone())),
+            _ => false,
+        };
 
-            self.run();
+        if revisited {
+            return Some(Outcome::InfiniteLoop {
+                acc: self.registers.acc,
+                ip: self.instruction_pointer,
+            });
+        }
 
-            if !self.did_break_on_inf_loop {
-                return;
-            }
+        // NOTE: A program whose registers never repeat would otherwise make
+        // `FullState` run forever, so `Auto` gets the same budget.
+        let step_limit = match (self.step_limit, policy, self.loop_policy) {
+            (None, LoopPolicy::StepBudget, _) => Some(cycle::DEFAULT_STEP_BUDGET),
+            (None, LoopPolicy::FullState, LoopPolicy::Auto) => Some(cycle::DEFAULT_STEP_BUDGET),
+            (step_limit, _, _) => step_limit,
+        };
 
-            self.did_break_on_inf_loop = false;
-            self.accumulator = 0;
-            self.instruction_pointer = 0;
-            self.completed_instructions = HashSet::new();
+        if step_limit == Some(self.steps) {
+            return Some(Outcome::StepLimit);
+        }
 
-            {
-                let op = self.ops.get_mut(instruction).unwrap();
-                // NOTE (Emil): Convert instruction back.
-                match op {
-                    Op::Nop(arg) => *op = Op::Jmp(*arg),
-                    Op::Jmp(arg) => *op = Op::Nop(*arg),
-                    _ => (),
-                }
+        self.completed_instructions.insert(self.instruction_pointer);
+        if policy == LoopPolicy::FullState {
+            self.completed_states.insert((self.instruction_pointer, self.registers.clone()));
+        }
+        self.steps += 1;
+
+        let acc_before = self.registers.acc;
+        let op = &self.ops[self.instruction_pointer];
+        let next = op.execute(&mut self.registers, &mut self.output);
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(Step {
+                ip: self.instruction_pointer,
+                op: op.clone(),
+                acc_before,
+                acc_after: self.registers.acc,
+            });
+        }
+
+        let offset = match next {
+            Next::Offset(offset) => offset,
+            Next::Halt => {
+                self.halted = true;
+                return None;
             }
+        };
+
+        let target = self.instruction_pointer as isize + offset as isize;
+        if target < 0 || target > self.ops.len() as isize {
+            return Some(Outcome::OutOfBounds { ip: target });
         }
-    }
 
-    fn find_jumps_and_nops(&self) -> Vec<usize> {
-        let mut instructions = vec![];
+        self.instruction_pointer = target as usize;
+
+        None
+    }
 
-        for (i, op) in self.ops.iter().enumerate() {
-            match op {
-                Op::Nop(_) => instructions.push(i),
-                Op::Jmp(_) => instructions.push(i),
-                _ => ()
+    /// Flips one `jmp`/`nop` at a time until the program terminates, returning
+    /// the flipped instruction and the accumulator of the first repair that does.
+    ///
+    /// Only instructions the program actually reaches can matter, so the
+    /// original program is run from the current state and each `jmp`/`nop` it
+    /// is about to execute is tried in a fork, which continues from there
+    /// rather than from the start.
+    fn try_repair_code(&mut self) -> Option<(usize, i32)> {
+        for (instruction, mut fork) in self.repair_candidates() {
+            if let Outcome::Terminated { acc } = fork.run() {
+                return Some((instruction, acc));
             }
         }
 
-        instructions
+        None
     }
 
-    fn nop(&mut self) {
-        self.instruction_pointer += 1;
+    /// Like `try_repair_code`, but runs the candidate forks on `threads`
+    /// threads. The result is the same: the first candidate in execution order
+    /// that terminates.
+    fn try_repair_code_parallel(&mut self, threads: usize) -> Option<(usize, i32)> {
+        let candidates = self.repair_candidates();
+        let chunk = candidates.len().div_ceil(threads.max(1)).max(1);
+
+        let mut candidates = candidates.into_iter();
+        let chunks: Vec<Vec<(usize, VM)>> = std::iter::from_fn(|| {
+            let chunk: Vec<(usize, VM)> = candidates.by_ref().take(chunk).collect();
+            if chunk.is_empty() { None } else { Some(chunk) }
+        })
+        .collect();
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        chunk.into_iter().find_map(|(instruction, mut fork)| match fork.run() {
+                            Outcome::Terminated { acc } => Some((instruction, acc)),
+                            _ => None,
+                        })
+                    })
+                })
+                .collect();
+
+            // NOTE: Chunks are in execution order, so the first chunk with a repair has the first repair.
+            handles.into_iter().find_map(|handle| handle.join().unwrap())
+        })
     }
 
-    fn acc(&mut self, arg: i32) {
-        self.accumulator += arg;
-        self.instruction_pointer += 1;
+    /// Runs the program to the end, forking just before every `jmp`/`nop` it
+    /// executes with that instruction flipped. The forks haven't run it yet.
+    fn repair_candidates(&mut self) -> Vec<(usize, VM)> {
+        let mut candidates = vec![];
+
+        loop {
+            if let Some(op) = self.ops.get(self.instruction_pointer) {
+                if !self.halted
+                    && op.flipped().is_some()
+                    && !self.completed_instructions.contains(&self.instruction_pointer)
+                {
+                    let mut fork = self.fork();
+                    fork.flip(self.instruction_pointer);
+                    candidates.push((self.instruction_pointer, fork));
+                }
+            }
+
+            if self.step().is_some() {
+                return candidates;
+            }
+        }
     }
 
-    fn jmp(&mut self, arg: i32) {
-        let uarg = arg as usize;
-        if arg.is_negative() {
-            self.instruction_pointer -= arg.wrapping_abs() as u32 as usize;
-        } else {
-            self.instruction_pointer += uarg;
+    fn flip(&mut self, instruction: usize) {
+        if let Some(flipped) = self.ops[instruction].flipped() {
+            self.patch(instruction, flipped);
         }
     }
 }
 
+/// The smallest dialect that has every instruction in `ops`.
+fn dialect_of(ops: &[Op]) -> Dialect {
+    if ops.iter().all(|op| op.dialect() == Dialect::Boot) {
+        Dialect::Boot
+    } else {
+        Dialect::Extended
+    }
+}
+
+enum Command {
+    Run,
+    Disassemble,
+    Debug,
+    Analyze,
+    Repair,
+    Trace,
+    Replay,
+    Diff,
+    Fuzz,
+}
+
+struct Options {
+    command: Command,
+    input: String,
+    dialect: Dialect,
+    step_limit: Option<usize>,
+    loop_policy: LoopPolicy,
+    output: Option<String>,
+    repair: bool,
+    brute_force: bool,
+    threads: usize,
+    dot: bool,
+    seek: Option<usize>,
+    seed: u64,
+    iterations: u64,
+    max_len: usize,
+    traces: Vec<String>,
+}
+
 fn main() -> io::Result<()> {
-    let f = File::open("src/day8/input_day8.txt")?;
-    let f = BufReader::new(f);
+    let args: Vec<String> = env::args().collect();
+    let options = parse_options(&args);
+
+    match options.command {
+        Command::Replay | Command::Diff => return compare_traces(&options),
+        Command::Fuzz => {
+            match fuzz::fuzz(options.seed, options.iterations, options.max_len) {
+                Ok(0) => println!("{} seeds passed", options.iterations),
+                Ok(uncorrupted) => println!(
+                    "{} seeds passed, {} without a corrupted program",
+                    options.iterations, uncorrupted
+                ),
+                Err((seed, ops, message)) => {
+                    println!("seed {}: {}", seed, message);
+                    print!("{}", asm::disassemble(&ops));
+                    std::process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        _ => (),
+    }
 
-    let mut ops1 = vec![];
+    let mut f = File::open(&options.input)?;
+    let mut s = String::new();
+    f.read_to_string(&mut s)?;
 
-    for line in f.lines() {
-        let line = line.unwrap();
-        let parts: Vec<&str> = line.split(" ").collect();
+    let ops1 = match asm::assemble(&s, options.dialect) {
+        Ok(ops) => ops,
+        Err(error) => {
+            eprintln!("{}: {}", options.input, error);
+            std::process::exit(1);
+        }
+    };
 
-        let op_name = parts[0];
-        let arg: i32 = parts[1].parse().unwrap();
+    match options.command {
+        Command::Run => {
+            let repair = repair::repair(&ops1);
+            let (outcome, output) = part1_run(ops1, options.step_limit, options.loop_policy);
 
-        let op = match op_name {
-            "acc" => Op::Acc(arg),
-            "nop" => Op::Nop(arg),
-            "jmp" => Op::Jmp(arg),
-            _ => panic!("Unsupported op!"),
-        };
+            println!("Part 1: {}", outcome);
+            if !output.is_empty() {
+                let output: Vec<String> = output.iter().map(|n| n.to_string()).collect();
+                println!("Output: {}", output.join(" "));
+            }
+            match repair {
+                repair::Repair::Unique { acc, .. } => println!("Part 2: {}", acc),
+                repair => println!("Part 2: {}", repair),
+            }
+        }
+        Command::Analyze => {
+            let analysis = analysis::analyze(&ops1);
+            if options.dot {
+                print!("{}", analysis.to_dot(&ops1));
+            } else {
+                print!("{}", analysis.report());
+            }
+        }
+        Command::Repair => {
+            if options.brute_force {
+                match part2_run(ops1, options.step_limit, options.threads) {
+                    Some((instruction, acc)) => println!(
+                        "flipping instruction {} terminates with accumulator {}",
+                        instruction, acc
+                    ),
+                    None => println!("no single jmp/nop flip makes the program terminate"),
+                }
+            } else {
+                println!("{}", repair::repair(&ops1));
+            }
+        }
+        Command::Disassemble => print!("{}", asm::disassemble(&ops1)),
+        Command::Debug => {
+            let mut vm = VM::new(ops1);
+            vm.set_step_limit(options.step_limit);
+            vm.set_loop_policy(options.loop_policy);
+
+            let stdin = io::stdin();
+            Debugger::new(vm).run(stdin.lock(), io::stdout())?;
+        }
+        Command::Trace => {
+            let mut vm = VM::new(ops1);
+            vm.set_step_limit(options.step_limit);
+            vm.set_loop_policy(options.loop_policy);
+
+            if options.repair {
+                match repair::repair(&vm.ops) {
+                    repair::Repair::Unique { instruction, .. } => {
+                        eprintln!("tracing with instruction {} flipped", instruction);
+                        vm.flip(instruction);
+                    }
+                    repair => eprintln!("not repairing: {}", repair),
+                }
+            }
+
+            let tracer = match &options.output {
+                Some(path) => Tracer::file(path)?,
+                None => Tracer::memory(),
+            };
+            vm.set_tracer(Some(tracer));
 
-        ops1.push(op);
+            let outcome = vm.run();
+            for step in vm.set_tracer(None).unwrap().finish()? {
+                println!("{}", step.to_line());
+            }
+            eprintln!("{} after {} steps", outcome, vm.steps);
+        }
+        Command::Replay | Command::Diff | Command::Fuzz => unreachable!(),
     }
 
-    let ops2 = ops1.clone();
+    Ok(())
+}
 
-    let accumulator1 = part1_run(ops1);
-    let accumulator2 = part2_run(ops2);
+fn compare_traces(options: &Options) -> io::Result<()> {
+    let mut traces = vec![];
+    for path in &options.traces {
+        let mut f = File::open(path)?;
+        let mut s = String::new();
+        f.read_to_string(&mut s)?;
+
+        match trace::parse_trace(&s) {
+            Ok(steps) => traces.push(steps),
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                std::process::exit(1);
+            }
+        }
+    }
 
-    println!("Part 1: {}", accumulator1);
-    println!("Part 2: {}", accumulator2);
+    match (&options.command, traces.as_slice()) {
+        (Command::Replay, [steps]) => match options.seek {
+            Some(index) => println!("{}", trace::seek(steps, index)),
+            None => {
+                println!("{} steps", steps.len());
+                if let Some(last) = steps.len().checked_sub(1) {
+                    println!("{}", trace::seek(steps, last));
+                }
+            }
+        },
+        (Command::Diff, [a, b]) => println!("{}", trace::diff(a, b)),
+        (Command::Replay, _) => eprintln!("replay takes exactly one trace file"),
+        _ => eprintln!("diff takes exactly two trace files"),
+    }
 
     Ok(())
 }
 
-fn part1_run(ops: Vec<Op>) -> i32 {
+const USAGE: &str = "\
+usage: day8 [run|disasm|debug] [--input <file>] [--dialect boot|extended] [--step-limit <n>]
+           [--loop-policy auto|first-revisit|full-state|budget|floyd|brent]
+       day8 analyze [--input <file>] [--dot]
+       day8 repair [--input <file>] [--brute-force [--threads <n>]]
+       day8 trace [--input <file>] [--repair] [--output <file>] [--loop-policy <policy>]
+       day8 replay <trace> [--seek <step>]
+       day8 diff <trace a> <trace b>
+       day8 fuzz [--seed <n>] [--iterations <n>] [--max-len <n>]";
+
+/// Parses the arguments shown in `USAGE`. Anything that isn't a command or
+/// an option is taken as a trace file, and unknown options print the usage.
+fn parse_options(args: &[String]) -> Options {
+    let mut command = Command::Run;
+    let mut input = String::from("src/day8/input_day8.txt");
+    let mut dialect = Dialect::Boot;
+    let mut step_limit = None;
+    let mut loop_policy = LoopPolicy::Auto;
+    let mut output = None;
+    let mut repair = false;
+    let mut brute_force = false;
+    let mut threads = 1;
+    let mut dot = false;
+    let mut seek = None;
+    let mut seed = 0;
+    let mut iterations = 1000;
+    let mut max_len = 50;
+    let mut traces = vec![];
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                if let Some(path) = args.next() {
+                    input = path.clone();
+                }
+            }
+            "--dialect" => {
+                if let Some(d) = args.next().and_then(|name| Dialect::from_name(name)) {
+                    dialect = d;
+                }
+            }
+            "--step-limit" => step_limit = args.next().and_then(|n| n.parse().ok()),
+            "--loop-policy" => {
+                if let Some(policy) = args.next().and_then(|name| LoopPolicy::from_name(name)) {
+                    loop_policy = policy;
+                }
+            }
+            "run" => command = Command::Run,
+            "disasm" => command = Command::Disassemble,
+            "debug" => command = Command::Debug,
+            "analyze" => command = Command::Analyze,
+            "repair" => command = Command::Repair,
+            "trace" => command = Command::Trace,
+            "replay" => command = Command::Replay,
+            "diff" => command = Command::Diff,
+            "--output" => output = args.next().cloned(),
+            "--repair" => repair = true,
+            "--brute-force" => brute_force = true,
+            "--threads" => threads = args.next().and_then(|n| n.parse().ok()).unwrap_or(1),
+            "--dot" => dot = true,
+            "--seek" => seek = args.next().and_then(|n| n.parse().ok()),
+            "fuzz" => command = Command::Fuzz,
+            "--seed" => seed = args.next().and_then(|n| n.parse().ok()).unwrap_or(0),
+            "--iterations" => iterations = args.next().and_then(|n| n.parse().ok()).unwrap_or(1000),
+            "--max-len" => max_len = args.next().and_then(|n| n.parse().ok()).unwrap_or(50),
+            flag if flag.starts_with("--") => {
+                eprintln!("Unknown option {}", flag);
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+            path => traces.push(path.to_string()),
+        }
+    }
+
+    Options { command, input, dialect, step_limit, loop_policy, output, repair, brute_force, threads, dot, seek, seed, iterations, max_len, traces }
+}
+
+fn part1_run(ops: Vec<Op>, step_limit: Option<usize>, loop_policy: LoopPolicy) -> (Outcome, Vec<i32>) {
     let mut vm = VM::new(ops);
-    vm.run();
+    vm.set_step_limit(step_limit);
+    vm.set_loop_policy(loop_policy);
 
-    vm.get_accumulator()
+    (vm.run(), vm.output)
 }
 
-fn part2_run(ops: Vec<Op>) -> i32 {
+fn part2_run(ops: Vec<Op>, step_limit: Option<usize>, threads: usize) -> Option<(usize, i32)> {
     let mut vm = VM::new(ops);
-    vm.try_repair_code();
+    vm.set_step_limit(step_limit);
+
+    if threads > 1 {
+        vm.try_repair_code_parallel(threads)
+    } else {
+        vm.try_repair_code()
+    }
+}
 
-    vm.get_accumulator()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<Op> {
+        vec![
+            Op::Nop(0),
+            Op::Acc(1),
+            Op::Jmp(4),
+            Op::Acc(3),
+            Op::Jmp(-3),
+            Op::Acc(-99),
+            Op::Acc(1),
+            Op::Jmp(-4),
+            Op::Acc(6),
+        ]
+    }
+
+    #[test]
+    fn test_example() {
+        let mut vm = VM::new(example());
+        assert_eq!(vm.run(), Outcome::InfiniteLoop { acc: 5, ip: 1 });
+
+        let mut vm = VM::new(example());
+        assert_eq!(vm.try_repair_code(), Some((7, 8)));
+    }
+
+    #[test]
+    fn test_last_instruction_runs() {
+        let mut vm = VM::new(vec![Op::Acc(1), Op::Acc(2)]);
+        assert_eq!(vm.run(), Outcome::Terminated { acc: 3 });
+
+        let mut vm = VM::new(vec![]);
+        assert_eq!(vm.run(), Outcome::Terminated { acc: 0 });
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut vm = VM::new(vec![Op::Acc(1), Op::Jmp(-2)]);
+        assert_eq!(vm.run(), Outcome::OutOfBounds { ip: -1 });
+
+        let mut vm = VM::new(vec![Op::Jmp(2)]);
+        assert_eq!(vm.run(), Outcome::OutOfBounds { ip: 2 });
+
+        let mut vm = VM::new(vec![Op::Jmp(1)]);
+        assert_eq!(vm.run(), Outcome::Terminated { acc: 0 });
+    }
+
+    #[test]
+    fn test_extended() {
+        // NOTE: Counts down from 3, printing each value, then halts before the trailing jmp.
+        let ops = vec![Op::Acc(3), Op::Out, Op::Acc(-1), Op::Jnz(-2), Op::Halt, Op::Jmp(0)];
+        let mut vm = VM::new(ops);
+        assert_eq!(vm.run(), Outcome::Terminated { acc: 0 });
+        assert_eq!(vm.output, vec![3, 2, 1]);
+
+        // NOTE: Revisiting an address with the same registers is still a loop.
+        let mut vm = VM::new(vec![Op::Acc(1), Op::Jnz(0)]);
+        assert_eq!(vm.run(), Outcome::InfiniteLoop { acc: 1, ip: 1 });
+    }
+
+    #[test]
+    fn test_snapshot_and_fork() {
+        let mut vm = VM::new(example());
+        vm.step();
+        vm.step();
+        let snapshot = vm.snapshot();
+
+        let mut fork = vm.fork();
+        fork.flip(7);
+        assert_eq!(fork.run(), Outcome::Terminated { acc: 8 });
+        assert_eq!(vm.ops[7], Op::Jmp(-4));
+        assert!(Arc::ptr_eq(&vm.ops, &snapshot.ops));
+
+        assert_eq!(vm.run(), Outcome::InfiniteLoop { acc: 5, ip: 1 });
+        vm.restore(&snapshot);
+        assert_eq!((vm.instruction_pointer, vm.registers.acc, vm.steps), (2, 1, 2));
+        assert_eq!(vm.run(), Outcome::InfiniteLoop { acc: 5, ip: 1 });
+    }
+
+    #[test]
+    fn test_parallel_repair() {
+        let ops = asm::assemble(include_str!("input_day8.txt"), Dialect::Boot).unwrap();
+        let mut vm = VM::new(ops.clone());
+        let expected = vm.try_repair_code();
+
+        for threads in 1..5 {
+            assert_eq!(VM::new(ops.clone()).try_repair_code_parallel(threads), expected);
+        }
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut vm = VM::new(example());
+        vm.set_step_limit(Some(3));
+        assert_eq!(vm.run(), Outcome::StepLimit);
+    }
 }
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::isa::{Dialect, Op};
+use crate::{Outcome, VM};
+
+#[derive(Debug, PartialEq)]
+pub enum Repair {
+    /// The program terminates without any changes.
+    NotNeeded { acc: i32 },
+    /// Exactly one `jmp`/`nop` flip makes the program terminate.
+    Unique { instruction: usize, acc: i32 },
+    /// Several flips make the program terminate, so the repair is ambiguous.
+    Multiple { instructions: Vec<usize> },
+    /// No single flip makes the program terminate.
+    Impossible,
+    /// The program uses an instruction outside the boot dialect, whose
+    /// conditional jumps this analysis can't follow.
+    Unsupported { instruction: usize },
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Repair::NotNeeded { acc } => write!(f, "already terminates with accumulator {}", acc),
+            Repair::Unique { instruction, acc } => write!(
+                f,
+                "flipping instruction {} terminates with accumulator {}",
+                instruction, acc
+            ),
+            Repair::Multiple { instructions } => {
+                let instructions: Vec<String> = instructions.iter().map(|i| i.to_string()).collect();
+                write!(f, "ambiguous, flipping any of {} terminates", instructions.join(", "))
+            }
+            Repair::Impossible => write!(f, "no single jmp/nop flip makes the program terminate"),
+            Repair::Unsupported { instruction } => {
+                write!(f, "instruction {} is not part of the boot dialect, can't repair", instruction)
+            }
+        }
+    }
+}
+
+/// Finds every single `jmp`/`nop` flip that makes the program terminate in linear time.
+///
+/// Only instructions on the original execution path can change the outcome,
+/// and flipping one of them helps exactly when its new successor can reach
+/// the end of the program. Those instructions are found once by walking the
+/// control-flow graph backwards from the end.
+pub fn repair(ops: &[Op]) -> Repair {
+    if let Some(instruction) = ops.iter().position(|op| op.dialect() != Dialect::Boot) {
+        return Repair::Unsupported { instruction };
+    }
+
+    let end = ops.len();
+
+    let mut predecessors: Vec<Vec<usize>> = vec![vec![]; end + 1];
+    for (i, op) in ops.iter().enumerate() {
+        if let Some(next) = successor(i, op, end) {
+            predecessors[next].push(i);
+        }
+    }
+
+    let mut terminating = vec![false; end + 1];
+    terminating[end] = true;
+    let mut stack = vec![end];
+    while let Some(current) = stack.pop() {
+        for predecessor in &predecessors[current] {
+            if !terminating[*predecessor] {
+                terminating[*predecessor] = true;
+                stack.push(*predecessor);
+            }
+        }
+    }
+
+    if terminating[0] {
+        return match run(ops.to_vec()) {
+            Outcome::Terminated { acc } => Repair::NotNeeded { acc },
+            _ => unreachable!(),
+        };
+    }
+
+    let mut candidates = vec![];
+    let mut visited = HashSet::new();
+    let mut ip = 0;
+    while ip < end && visited.insert(ip) {
+        if let Some(flipped) = ops[ip].flipped() {
+            if let Some(next) = successor(ip, &flipped, end) {
+                if terminating[next] {
+                    candidates.push(ip);
+                }
+            }
+        }
+
+        ip = match successor(ip, &ops[ip], end) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    match candidates.as_slice() {
+        [] => Repair::Impossible,
+        [instruction] => {
+            let mut ops = ops.to_vec();
+            ops[*instruction] = ops[*instruction].flipped().unwrap();
+
+            match run(ops) {
+                Outcome::Terminated { acc } => Repair::Unique { instruction: *instruction, acc },
+                _ => unreachable!(),
+            }
+        }
+        _ => Repair::Multiple { instructions: candidates },
+    }
+}
+
+/// The instruction executed after `ip`, or `None` when it jumps out of the program.
+fn successor(ip: usize, op: &Op, end: usize) -> Option<usize> {
+    let next = op.targets(ip)[0];
+    if next < 0 || next > end as isize {
+        None
+    } else {
+        Some(next as usize)
+    }
+}
+
+fn run(ops: Vec<Op>) -> Outcome {
+    VM::new(ops).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::asm;
+
+    #[test]
+    fn test_example() {
+        let ops = asm::assemble(include_str!("example_day8.txt"), Dialect::Boot).unwrap();
+
+        assert_eq!(repair(&ops), Repair::Unique { instruction: 7, acc: 8 });
+    }
+
+    #[test]
+    fn test_not_needed() {
+        let ops = vec![Op::Acc(3), Op::Jmp(2), Op::Acc(100), Op::Acc(1)];
+
+        assert_eq!(repair(&ops), Repair::NotNeeded { acc: 4 });
+    }
+
+    #[test]
+    fn test_multiple() {
+        // NOTE: Jumping out of the loop from 0 or falling through at 2 both reach the end.
+        let ops = vec![Op::Nop(3), Op::Jmp(1), Op::Jmp(-1)];
+
+        assert_eq!(repair(&ops), Repair::Multiple { instructions: vec![0, 2] });
+    }
+
+    #[test]
+    fn test_impossible() {
+        let ops = vec![Op::Acc(1), Op::Acc(1), Op::Jmp(-1), Op::Jmp(-3)];
+
+        assert_eq!(repair(&ops), Repair::Impossible);
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let ops = vec![Op::Acc(2), Op::Jmp(-5), Op::Acc(1)];
+
+        assert_eq!(repair(&ops), Repair::Unique { instruction: 1, acc: 3 });
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let ops = vec![Op::Acc(1), Op::Jnz(-1), Op::Jmp(-2)];
+
+        assert_eq!(repair(&ops), Repair::Unsupported { instruction: 1 });
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let mut vm = VM::new(asm::assemble(include_str!("input_day8.txt"), Dialect::Boot).unwrap());
+        let (instruction, acc) = vm.try_repair_code().unwrap();
+
+        assert_eq!(repair(&vm.ops), Repair::Unique { instruction, acc });
+    }
+}
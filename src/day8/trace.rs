@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::isa::{Dialect, Op};
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub ip: usize,
+    pub op: Op,
+    pub acc_before: i32,
+    pub acc_after: i32,
+}
+
+impl Step {
+    /// Formats the step as `<ip> <op> <acc before> <acc after>`, e.g. `2 jmp +4 1 1`.
+    pub fn to_line(&self) -> String {
+        format!("{} {} {} {}", self.ip, self.op, self.acc_before, self.acc_after)
+    }
+
+    pub fn from_line(line: &str) -> Result<Step, String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 4 {
+            return Err(format!("expected '<ip> <op> <acc before> <acc after>', got '{}'", line));
+        }
+
+        let number = |s: &str| -> Result<i32, String> { s.parse().map_err(|_| format!("invalid number '{}'", s)) };
+
+        let ip = parts[0].parse().map_err(|_| format!("invalid address '{}'", parts[0]))?;
+        let op = Op::parse(&parts[1..parts.len() - 2].join(" "), Dialect::Extended)?;
+
+        Ok(Step {
+            ip,
+            op,
+            acc_before: number(parts[parts.len() - 2])?,
+            acc_after: number(parts[parts.len() - 1])?,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Sink {
+    Memory(Vec<Step>),
+    File(BufWriter<File>),
+}
+
+/// Records the steps a `VM` executes, either in memory or straight to a file.
+#[derive(Debug)]
+pub struct Tracer {
+    sink: Sink,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn memory() -> Tracer {
+        Tracer {
+            sink: Sink::Memory(vec![]),
+            error: None,
+        }
+    }
+
+    pub fn file(path: &str) -> io::Result<Tracer> {
+        Ok(Tracer {
+            sink: Sink::File(BufWriter::new(File::create(path)?)),
+            error: None,
+        })
+    }
+
+    pub fn record(&mut self, step: Step) {
+        match &mut self.sink {
+            Sink::Memory(steps) => steps.push(step),
+            Sink::File(writer) => {
+                // NOTE: Only the first write error is kept, it is returned by `finish`.
+                if self.error.is_none() {
+                    if let Err(error) = writeln!(writer, "{}", step.to_line()) {
+                        self.error = Some(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the recorded steps, or flushes the file and returns nothing.
+    pub fn finish(self) -> io::Result<Vec<Step>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        match self.sink {
+            Sink::Memory(steps) => Ok(steps),
+            Sink::File(mut writer) => {
+                writer.flush()?;
+                Ok(vec![])
+            }
+        }
+    }
+}
+
+/// Reads a trace written by `Tracer::file`, skipping blank lines.
+pub fn parse_trace(s: &str) -> Result<Vec<Step>, String> {
+    let mut steps = vec![];
+
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let step = Step::from_line(line).map_err(|message| format!("line {}: {}", i + 1, message))?;
+        steps.push(step);
+    }
+
+    Ok(steps)
+}
+
+/// The index of the first step where the traces differ, or where the
+/// shorter one ends. `None` when the traces are identical.
+pub fn first_divergence(a: &[Step], b: &[Step]) -> Option<usize> {
+    match a.iter().zip(b.iter()).position(|(a, b)| a != b) {
+        Some(i) => Some(i),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// Describes the step at `index` and the state it left the program in.
+pub fn seek(steps: &[Step], index: usize) -> String {
+    match steps.get(index) {
+        Some(step) => {
+            let next_ip = steps.get(index + 1).map(|next| next.ip.to_string());
+            format!(
+                "step {}: {} at {}, acc {} -> {}, next ip {}",
+                index,
+                step.op,
+                step.ip,
+                step.acc_before,
+                step.acc_after,
+                next_ip.unwrap_or_else(|| String::from("(end of trace)"))
+            )
+        }
+        None => format!("step {} is past the end of the trace ({} steps)", index, steps.len()),
+    }
+}
+
+/// Summarises where two traces part ways.
+pub fn diff(a: &[Step], b: &[Step]) -> String {
+    let index = match first_divergence(a, b) {
+        Some(index) => index,
+        None => return format!("traces are identical ({} steps)", a.len()),
+    };
+
+    let describe = |steps: &[Step]| match steps.get(index) {
+        Some(step) => step.to_line(),
+        None => String::from("(end of trace)"),
+    };
+
+    format!(
+        "traces diverge at step {}\n  a: {}\n  b: {}\n{} steps in a, {} steps in b",
+        index,
+        describe(a),
+        describe(b),
+        a.len(),
+        b.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{asm, VM};
+
+    fn trace(ops: Vec<Op>) -> Vec<Step> {
+        let mut vm = VM::new(ops);
+        vm.set_tracer(Some(Tracer::memory()));
+        vm.run();
+
+        vm.set_tracer(None).unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_trace_round_trip() {
+        let ops = asm::assemble(include_str!("example_day8.txt"), Dialect::Boot).unwrap();
+        let steps = trace(ops);
+
+        assert_eq!(steps.len(), 7);
+        assert_eq!(steps[1].to_line(), "1 acc +1 0 1");
+        assert_eq!(steps[2].to_line(), "2 jmp +4 1 1");
+
+        let lines: Vec<String> = steps.iter().map(|step| step.to_line()).collect();
+        assert_eq!(parse_trace(&lines.join("\n")), Ok(steps));
+
+        let steps = trace(vec![Op::Acc(2), Op::Sta(1), Op::Out, Op::Halt]);
+        assert_eq!(steps[1].to_line(), "1 sta r1 2 2");
+        assert_eq!(steps[3].to_line(), "3 halt 2 2");
+
+        let lines: Vec<String> = steps.iter().map(|step| step.to_line()).collect();
+        assert_eq!(parse_trace(&lines.join("\n")), Ok(steps));
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut ops = asm::assemble(include_str!("example_day8.txt"), Dialect::Boot).unwrap();
+        let original = trace(ops.clone());
+
+        ops[7] = Op::Nop(-4);
+        let repaired = trace(ops);
+
+        assert_eq!(first_divergence(&original, &original), None);
+        assert_eq!(first_divergence(&original, &repaired), Some(4));
+        assert_eq!(first_divergence(&original, &original[..3]), Some(3));
+
+        assert!(diff(&original, &repaired).starts_with("traces diverge at step 4\n  a: 7 jmp -4 2 2\n  b: 7 nop -4 2 2"));
+        assert_eq!(seek(&repaired, 5), "step 5: acc +6 at 8, acc 2 -> 8, next ip (end of trace)");
+    }
+}
@@ -1,12 +1,37 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{self, BufReader};
+use std::env;
+
+mod stream;
+mod weakness;
+mod xmas;
+
+enum Mode {
+    File,
+    Stream,
+}
+
+struct Options {
+    mode: Mode,
+    input: String,
+    preamble: usize,
+    history: usize,
+    all: bool,
+}
 
 fn main() -> io::Result<()> {
-    let f = File::open("src/day9/input_day9.txt")?;
+    let args: Vec<String> = env::args().collect();
+    let options = parse_options(&args);
+
+    if let Mode::Stream = options.mode {
+        let stdin = io::stdin();
+        return stream::decode(stdin.lock(), io::stdout(), options.preamble, options.history);
+    }
+
+    let f = File::open(&options.input)?;
     let f = BufReader::new(f);
 
-    let preamble_size = 25;
     let mut message: Vec<i64> = vec![];
 
     for line in f.lines() {
@@ -15,84 +40,72 @@ fn main() -> io::Result<()> {
         message.push(line.parse().unwrap());
     }
 
-    let mut first_invalid: i64 = 0;
-    for i in preamble_size..message.len() {
-        let n = message[i];
+    let invalid = xmas::invalid_numbers(&message, options.preamble);
+    for (position, n) in &invalid {
+        println!("Invalid entry at {}: {}", position, n);
+    }
 
-        let start = i - preamble_size;
-        let end = i;
-        if !validate(&message[start..end], n) {
-            first_invalid = n;
-            break;
+    let first_invalid = match invalid.first() {
+        Some((_, n)) => *n,
+        None => {
+            println!("Every entry is valid");
+            return Ok(());
         }
-    }
+    };
 
     println!("First invalid entry: {}", first_invalid);
 
-    let range = find_weakness(&mut message, first_invalid);
-    let range = &mut message[range.0..range.1];
-    range.sort();
+    if options.all {
+        for range in weakness::weaknesses(&message, first_invalid) {
+            let sum = weakness::encryption_weakness(&message, range.clone()).unwrap();
+            println!("Range {}..{}: {}", range.start, range.end, sum);
+        }
+    }
 
-    let smallest = range.first().unwrap();
-    let largest = range.last().unwrap();
-    println!(
-        "Smallest: {} Largest: {}, Sum: {}",
-        smallest,
-        largest,
-        smallest + largest
-    );
+    match weakness::find_weakness(&message, first_invalid) {
+        Ok(Some(range)) => {
+            let sum = weakness::encryption_weakness(&message, range.clone()).unwrap();
+            println!("Weakness {}..{}: {}", range.start, range.end, sum);
+        }
+        Ok(None) => println!("No contiguous range sums to {}", first_invalid),
+        Err(overflow) => println!("No weakness, {}", overflow),
+    }
 
     Ok(())
 }
 
-fn validate(numbers: &[i64], e: i64) -> bool {
-    for i in 0..numbers.len() {
-        for j in 0..numbers.len() {
-            if i == j {
-                continue;
+/// `day9 [--input <file>] [--preamble <n>] [--all]`
+/// `day9 stream [--preamble <n>] [--history <n>]`, reading numbers from stdin
+fn parse_options(args: &[String]) -> Options {
+    let mut mode = Mode::File;
+    let mut input = String::from("src/day9/input_day9.txt");
+    let mut preamble = 25;
+    let mut history = 1000;
+    let mut all = false;
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                if let Some(path) = args.next() {
+                    input = path.clone();
+                }
             }
-
-            let one = numbers[i];
-            let two = numbers[j];
-
-            let sum = one + two;
-
-            if sum == e {
-                return true;
+            "--preamble" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    preamble = n;
+                }
             }
-        }
-    }
-
-    false
-}
-
-fn find_weakness(numbers: &Vec<i64>, e: i64) -> (usize, usize) {
-    let mut range = (0, 0);
-    let mut sum = 0;
-
-    while sum != e {
-        sum = 0;
-
-        for i in range.0..numbers.len() {
-            let n = numbers[i];
-
-            sum += n;
-
-            if sum == e {
-                range.1 = i + 1;
-                return range;
-            } else if sum > e {
-                break;
+            "--history" => {
+                if let Some(n) = args.next().and_then(|n| n.parse().ok()) {
+                    history = n;
+                }
             }
-        }
-
-        range.0 += 1;
-        sum = 0;
-
-        if range.0 == numbers.len() {
-            return (0, 0);
+            "--all" => all = true,
+            "stream" => mode = Mode::Stream,
+            _ => (),
         }
     }
 
-    range
+    Options { mode, input, preamble, history, all }
 }
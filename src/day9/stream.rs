@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use crate::weakness;
+use crate::xmas::XmasStream;
+
+/// Decodes numbers one per line from `input` as they arrive, writing every
+/// invalid number to `output` the moment it is read.
+///
+/// Only the preamble window and the last `history` numbers are kept. Each
+/// invalid number is followed by the weakness found in that history, with
+/// positions counted from the start of the stream.
+pub fn decode<R: BufRead, W: Write>(input: R, mut output: W, preamble: usize, history: usize) -> io::Result<()> {
+    let mut stream = XmasStream::new(preamble);
+    let mut recent: VecDeque<i64> = VecDeque::with_capacity(history + 1);
+
+    // NOTE: Blank lines are skipped, so positions count the numbers read
+    // while errors refer to lines.
+    let mut position = 0;
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let n: i64 = match line.parse() {
+            Ok(n) => n,
+            Err(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: '{}' is not an i64", number + 1, line),
+                ))
+            }
+        };
+
+        if !stream.push(n) {
+            writeln!(output, "Invalid entry at {}: {}", position, n)?;
+
+            // NOTE: `recent` ends just before the invalid number, which is at `position`.
+            let offset = position - recent.len();
+            let numbers = recent.make_contiguous();
+            match weakness::find_weakness(numbers, n) {
+                Ok(Some(range)) => {
+                    let sum = weakness::encryption_weakness(numbers, range.clone()).unwrap();
+                    writeln!(output, "  Weakness {}..{}: {}", range.start + offset, range.end + offset, sum)?;
+                }
+                Ok(None) => writeln!(output, "  No weakness in the last {} entries", numbers.len())?,
+                Err(overflow) => {
+                    let overflow = weakness::Overflow { position: overflow.position + offset };
+                    writeln!(output, "  No weakness, {}", overflow)?;
+                }
+            }
+        }
+
+        if history > 0 {
+            if recent.len() == history {
+                recent.pop_front();
+            }
+            recent.push_back(n);
+        }
+
+        position += 1;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_to_string(input: &str, preamble: usize, history: usize) -> io::Result<String> {
+        let mut output = vec![];
+        decode(input.as_bytes(), &mut output, preamble, history)?;
+
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_example() {
+        let output = decode_to_string(include_str!("example_day9.txt"), 5, 100).unwrap();
+        assert_eq!(output, "Invalid entry at 14: 127\n  Weakness 2..6: 62\n");
+
+        // NOTE: With only the last 8 numbers kept, 15 has already been forgotten.
+        let output = decode_to_string(include_str!("example_day9.txt"), 5, 8).unwrap();
+        assert_eq!(output, "Invalid entry at 14: 127\n  No weakness in the last 8 entries\n");
+
+        let input = format!("\n\n{}", include_str!("example_day9.txt").replace("\n", "\n\n"));
+        let output = decode_to_string(&input, 5, 100).unwrap();
+        assert_eq!(output, "Invalid entry at 14: 127\n  Weakness 2..6: 62\n");
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = format!("{}\n1\n{}\n5\n", i64::MAX - 1, i64::MAX);
+        let output = decode_to_string(&input, 2, 10).unwrap();
+        assert_eq!(output, "Invalid entry at 3: 5\n  No weakness in the last 3 entries\n");
+
+        // NOTE: The first number is too large to be part of any range.
+        let input = format!("{}\n2\n3\n5\n8\n10\n", i64::MAX - 1);
+        let output = decode_to_string(&input, 3, 10).unwrap();
+        assert_eq!(output, "Invalid entry at 5: 10\n  Weakness 1..4: 7\n");
+
+        assert!(decode_to_string("1\n2\nthree\n", 2, 10).is_err());
+        assert!(decode_to_string("1\n99999999999999999999\n", 2, 10).is_err());
+
+        let error = decode_to_string("\n1\n\nthree\n", 2, 10).unwrap_err();
+        assert_eq!(error.to_string(), "line 4: 'three' is not an i64");
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// A running sum left the range of `i64` while adding the number at `position`.
+#[derive(Debug, PartialEq)]
+pub struct Overflow {
+    pub position: usize,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "sum overflows i64 at position {}", self.position)
+    }
+}
+
+/// The first contiguous range of at least two numbers that sums to `target`.
+///
+/// Uses two pointers: the end of the window moves forward one number at a
+/// time and the start catches up whenever the sum overshoots, so every number
+/// is added and removed at most once. This relies on the numbers not being
+/// negative, which XMAS numbers never are.
+pub fn find_weakness(numbers: &[i64], target: i64) -> Result<Option<Range<usize>>, Overflow> {
+    let mut start = 0;
+    let mut sum: i64 = 0;
+
+    for (end, n) in numbers.iter().enumerate() {
+        // NOTE: The start catches up before `n` is added, so the sum never
+        // goes past the target on its way to a range that fits.
+        while start < end && target.checked_sub(*n).is_none_or(|limit| sum > limit) {
+            sum -= numbers[start];
+            start += 1;
+        }
+
+        sum = sum.checked_add(*n).ok_or(Overflow { position: end })?;
+
+        if sum == target && end > start {
+            return Ok(Some(start..end + 1));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Every contiguous range of at least two numbers that sums to `target`,
+/// ordered by where they start.
+///
+/// A range `i..j` sums to `target` exactly when the prefix sums before `i`
+/// and before `j` differ by `target`, so each end looks up the starts it
+/// pairs with in a map of earlier prefix sums. Unlike `find_weakness` this
+/// also works with negative numbers. The prefix sums are kept as `i128`, so
+/// they don't overflow however long the input is.
+pub fn weaknesses(numbers: &[i64], target: i64) -> Vec<Range<usize>> {
+    let mut prefix: Vec<i128> = vec![0];
+    for (position, n) in numbers.iter().enumerate() {
+        prefix.push(prefix[position] + *n as i128);
+    }
+
+    let mut starts: HashMap<i128, Vec<usize>> = HashMap::new();
+    let mut ranges = vec![];
+
+    for end in 2..prefix.len() {
+        // NOTE: Only starts at least two numbers back are eligible.
+        starts.entry(prefix[end - 2]).or_default().push(end - 2);
+
+        if let Some(found) = starts.get(&(prefix[end] - target as i128)) {
+            ranges.extend(found.iter().map(|start| *start..end));
+        }
+    }
+
+    ranges.sort_by_key(|range| (range.start, range.end));
+
+    ranges
+}
+
+/// The sum of the smallest and largest number in the range.
+pub fn encryption_weakness(numbers: &[i64], range: Range<usize>) -> Option<i64> {
+    let range = &numbers[range];
+    let smallest = range.iter().min()?;
+    let largest = range.iter().max()?;
+
+    Some(smallest + largest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<i64> {
+        include_str!("example_day9.txt").lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let numbers = example();
+
+        let range = find_weakness(&numbers, 127).unwrap().unwrap();
+        assert_eq!(range, 2..6);
+        assert_eq!(encryption_weakness(&numbers, range), Some(62));
+        assert_eq!(weaknesses(&numbers, 127), vec![Range { start: 2, end: 6 }]);
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        // NOTE: The single 5 doesn't count, and nothing sums to 100.
+        let numbers = [1, 5, 2, 3, 4];
+        assert_eq!(find_weakness(&numbers, 5), Ok(Some(2..4)));
+        assert_eq!(weaknesses(&numbers, 5), vec![Range { start: 2, end: 4 }]);
+        assert_eq!(find_weakness(&numbers, 100), Ok(None));
+        assert_eq!(weaknesses(&numbers, 100), vec![]);
+
+        assert_eq!(weaknesses(&[1, 2, 3, 3, -3, 3], 6), vec![0..3, 0..5, 2..4, 2..6]);
+        assert_eq!(encryption_weakness(&numbers, 0..0), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let numbers = [1, i64::MAX - 1, 2, 5];
+
+        assert_eq!(find_weakness(&numbers, i64::MAX), Ok(Some(0..2)));
+        assert_eq!(weaknesses(&numbers, i64::MAX), vec![0..2]);
+        assert_eq!(find_weakness(&numbers, 6), Ok(None));
+        assert_eq!(weaknesses(&numbers, 6), vec![]);
+
+        // NOTE: Only the numbers after the first sum to the target.
+        let numbers = [i64::MAX - 1, 2, 3];
+        assert_eq!(find_weakness(&numbers, 5), Ok(Some(1..3)));
+        assert_eq!(weaknesses(&numbers, 5), vec![1..3]);
+
+        // NOTE: All of the numbers add up to more than an i64 holds.
+        let mut numbers = vec![i64::MAX / 2; 5];
+        numbers.extend(&[1, 2, 3, 4]);
+        assert_eq!(find_weakness(&numbers, 7), Ok(Some(7..9)));
+        assert_eq!(weaknesses(&numbers, 7), vec![7..9]);
+        assert_eq!(find_weakness(&numbers, i64::MAX - 1), Ok(Some(0..2)));
+        assert_eq!(weaknesses(&numbers, i64::MAX - 1), vec![0..2, 1..3, 2..4, 3..5]);
+    }
+}
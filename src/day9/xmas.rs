@@ -0,0 +1,97 @@
+use std::collections::{HashMap, VecDeque};
+
+/// Checks XMAS numbers one at a time against the preamble window before them.
+///
+/// The window is kept both in order, to know which number drops out next,
+/// and as a multiset of counts, so checking whether a number is the sum of
+/// two others in the window takes one lookup per window entry instead of a
+/// scan over every pair.
+#[derive(Debug)]
+pub struct XmasStream {
+    preamble: usize,
+    window: VecDeque<i64>,
+    counts: HashMap<i64, usize>,
+}
+
+impl XmasStream {
+    pub fn new(preamble: usize) -> XmasStream {
+        XmasStream {
+            preamble,
+            window: VecDeque::with_capacity(preamble + 1),
+            counts: HashMap::new(),
+        }
+    }
+
+    /// Adds the next number, returning false if it isn't the sum of two
+    /// different entries in the window. Numbers in the preamble are always valid.
+    pub fn push(&mut self, n: i64) -> bool {
+        let valid = self.window.len() < self.preamble || self.is_sum(n);
+
+        self.window.push_back(n);
+        *self.counts.entry(n).or_insert(0) += 1;
+
+        if self.window.len() > self.preamble {
+            let old = self.window.pop_front().unwrap();
+            let count = self.counts.get_mut(&old).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&old);
+            }
+        }
+
+        valid
+    }
+
+    fn is_sum(&self, n: i64) -> bool {
+        self.window.iter().any(|a| match n.checked_sub(*a) {
+            // NOTE: A number may only pair with itself if it is in the window twice.
+            Some(b) if b == *a => self.counts[a] > 1,
+            Some(b) => self.counts.contains_key(&b),
+            None => false,
+        })
+    }
+}
+
+/// Every number that isn't the sum of two of the `preamble` numbers before it,
+/// with its position in the message.
+pub fn invalid_numbers(message: &[i64], preamble: usize) -> Vec<(usize, i64)> {
+    let mut stream = XmasStream::new(preamble);
+
+    message
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !stream.push(**n))
+        .map(|(i, n)| (i, *n))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Vec<i64> {
+        s.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_example() {
+        let message = parse(include_str!("example_day9.txt"));
+
+        assert_eq!(invalid_numbers(&message, 5), vec![(14, 127)]);
+    }
+
+    #[test]
+    fn test_window() {
+        // NOTE: 26 and 49 are valid, 100 has no pair and 3 would be 1 + 2,
+        // which have left the window by then.
+        let mut message: Vec<i64> = (1..=25).collect();
+        message.extend(&[26, 49, 100, 3]);
+        assert_eq!(invalid_numbers(&message, 25), vec![(27, 100), (28, 3)]);
+
+        let mut stream = XmasStream::new(2);
+        assert!(stream.push(25));
+        assert!(stream.push(25));
+        assert!(stream.push(50));
+        assert!(!stream.push(50));
+    }
+}